@@ -1,6 +1,14 @@
-use axum::{body::Bytes, extract::{Path, State}, routing::post, Router};
+use axum::{body::Bytes, extract::{Path, Query, State}, routing::post, Router};
 use reqwest::Client;
-use std::{path::Component, sync::Arc};
+use serde::Deserialize;
+use std::{
+    path::Component,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
 use tauri::{AppHandle, Manager};
 use tokio::{fs, sync::Mutex};
 
@@ -27,18 +35,167 @@ pub async fn serve(state: AppState) -> Result<(), String> {
     Ok(())
 }
 
-async fn decompile(State(state): State<AppState>, body: Bytes) -> String {
-    if *state.decompiler.lock().await == "medal" {
-        decompile_medal(body)
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    #[default]
+    Lua,
+    Disassembly,
+    /// The decompiled AST, for tools that analyze scripts
+    Json,
+}
+
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteStyle {
+    #[default]
+    Double,
+    Single,
+}
+
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+// how long a whole request can take, functions that aren't done by then are stubbed out
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(120);
+// how long to wait for the stubbed output once the request has been cancelled
+const TIMEOUT_GRACE: Duration = Duration::from_secs(5);
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct DecompileQuery {
+    pub format: OutputFormat,
+    /// Seconds each function can take to decompile before it's stubbed out
+    pub timeout: Option<u64>,
+    /// Seconds the whole request can take before the functions left are stubbed out
+    pub request_timeout: Option<u64>,
+    /// How many times the structuring passes can be repeated on each function
+    pub max_iterations: Option<usize>,
+    /// Indent with this many spaces instead of tabs
+    pub indent: Option<u8>,
+    pub max_width: Option<usize>,
+    pub quote_style: QuoteStyle,
+    pub hex_bit_masks: bool,
+    pub trailing_commas: bool,
+    pub blank_lines_between_functions: usize,
+}
+
+impl DecompileQuery {
+    fn timeout(&self) -> Duration {
+        self.timeout.map_or(DEFAULT_TIMEOUT, Duration::from_secs)
+    }
+
+    fn request_timeout(&self) -> Duration {
+        self.request_timeout.map_or(DEFAULT_REQUEST_TIMEOUT, Duration::from_secs)
+    }
+
+    fn decompile_options(&self, cancel: &Arc<AtomicBool>) -> luau_lifter::DecompileOptions {
+        let default_limits = luau_lifter::DecompileLimits::default();
+        luau_lifter::DecompileOptions {
+            format: luau_lifter::FormatOptions {
+                indentation_mode: match self.indent {
+                    Some(spaces) => luau_lifter::IndentationMode::Spaces(spaces),
+                    None => luau_lifter::IndentationMode::Tab,
+                },
+                max_width: self.max_width,
+                quote_style: match self.quote_style {
+                    QuoteStyle::Double => luau_lifter::QuoteStyle::Double,
+                    QuoteStyle::Single => luau_lifter::QuoteStyle::Single,
+                },
+                hex_bit_masks: self.hex_bit_masks,
+                trailing_commas: self.trailing_commas,
+                blank_lines_between_functions: self.blank_lines_between_functions,
+                ..Default::default()
+            },
+            limits: luau_lifter::DecompileLimits {
+                timeout: Some(self.timeout()),
+                max_iterations: self.max_iterations.unwrap_or(default_limits.max_iterations),
+                cancel: Some(cancel.clone()),
+            },
+            ..Default::default()
+        }
+    }
+}
+
+async fn decompile(
+    State(state): State<AppState>,
+    Query(query): Query<DecompileQuery>,
+    body: Bytes,
+) -> String {
+    let cancel = Arc::new(AtomicBool::new(false));
+    let options = query.decompile_options(&cancel);
+    let timeout = query.request_timeout();
+    if query.format == OutputFormat::Disassembly {
+        run_blocking(cancel, timeout, move || disassemble_medal(body))
+            .await
+            .unwrap_or_else(|err| format!("-- {}", err))
+    } else if query.format == OutputFormat::Json {
+        run_blocking(cancel, timeout, move || decompile_medal_json(body, &options))
+            .await
+            .unwrap_or_else(|err| serde_json::json!({ "error": err }).to_string())
+    } else if *state.decompiler.lock().await == "medal" {
+        run_blocking(cancel, timeout, move || decompile_medal(body, &options))
+            .await
+            .unwrap_or_else(|err| format!("-- {}", err))
     } else {
         decompile_konstant(state.app_handle, body).await
     }
 }
 
-pub fn decompile_medal(bytecode: Bytes) -> String {
-    let decompiled = luau_lifter::decompile_bytecode(&bytecode, 203);
-    match decompiled {
+// medal is cpu bound so it runs on the blocking pool instead of starving the runtime.
+// once the request runs out of `timeout`, `cancel` is set, which makes `f` stub out the functions
+// it hasn't decompiled yet and return
+async fn run_blocking(
+    cancel: Arc<AtomicBool>,
+    timeout: Duration,
+    f: impl FnOnce() -> String + Send + 'static,
+) -> Result<String, String> {
+    let mut task = tokio::task::spawn_blocking(f);
+    let result = match tokio::time::timeout(timeout, &mut task).await {
+        Ok(result) => result,
+        Err(_) => {
+            cancel.store(true, Ordering::Relaxed);
+            tokio::time::timeout(TIMEOUT_GRACE, task)
+                .await
+                .map_err(|_| "decompilation timed out".to_string())?
+        }
+    };
+    result.map_err(|err| err.to_string())
+}
+
+pub fn decompile_medal(bytecode: Bytes, options: &luau_lifter::DecompileOptions) -> String {
+    format_medal_result(luau_lifter::decompile_bytecode_with_options(
+        &bytecode,
+        luau_lifter::DETECT_ENCODE_KEY,
+        options,
+    ))
+}
+
+pub fn decompile_medal_json(bytecode: Bytes, options: &luau_lifter::DecompileOptions) -> String {
+    match luau_lifter::decompile_bytecode_ast(&bytecode, luau_lifter::DETECT_ENCODE_KEY, options) {
+        Ok(chunk) => serde_json::to_string(&chunk).unwrap(),
+        Err(err) => serde_json::json!({ "error": err.to_string() }).to_string(),
+    }
+}
+
+pub fn disassemble_medal(bytecode: Bytes) -> String {
+    format_medal_result(luau_lifter::disassemble_bytecode(
+        &bytecode,
+        luau_lifter::DETECT_ENCODE_KEY,
+    ))
+}
+
+fn format_medal_result(result: Result<String, luau_lifter::DeserializeError>) -> String {
+    match result {
         Ok(script) => script,
+        Err(luau_lifter::DeserializeError::Invalid {
+            offset,
+            function,
+            reason,
+        }) => format!(
+            "-- Error occured while parsing bytecode\n-- offset: {:#x}\n-- function: {}\n-- reason: {}",
+            offset,
+            function.map_or_else(|| "none".to_string(), |f| f.to_string()),
+            reason
+        ),
         Err(err) => format!(
             "-- Error occured while decompiling, error:\n\n--[[\n{}\n--]]",
             err
@@ -1,6 +1,6 @@
 use std::fmt;
 
-use crate::{formatter::Formatter, RcLocal, SideEffects, Traverse};
+use crate::{formatter::Formatter, RcLocal, SideEffects, Traverse, Type};
 
 use super::{LValue, LocalRw, RValue};
 
@@ -10,6 +10,10 @@ pub struct Assign {
     pub right: Vec<RValue>,
     pub prefix: bool,
     pub parallel: bool,
+    pub line: Option<usize>,
+    // the types `left` is annotated with if it's a local declaration, empty if there
+    // aren't any
+    pub types: Vec<Type>,
 }
 
 impl Assign {
@@ -19,6 +23,8 @@ impl Assign {
             right,
             prefix: false,
             parallel: false,
+            line: None,
+            types: Vec::new(),
         }
     }
 }
@@ -77,8 +83,10 @@ impl fmt::Display for Assign {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         Formatter {
             indentation_level: 0,
-            indentation_mode: Default::default(),
+            options: Default::default(),
             output: f,
+            last_line: None,
+            position: None,
         }
         .format_assign(self)
     }
@@ -1,6 +1,9 @@
 use std::fmt;
 
-use crate::{Literal, LocalRw, RValue, RcLocal, Reduce, SideEffects, Traverse};
+use crate::{
+    type_system::{Infer, TypeSystem},
+    Literal, LocalRw, RValue, RcLocal, Reduce, SideEffects, Traverse, Type,
+};
 
 use super::{Unary, UnaryOperation};
 
@@ -94,6 +97,31 @@ impl SideEffects for Binary {
     }
 }
 
+impl Infer for Binary {
+    fn infer(&self, system: &TypeSystem) -> Type {
+        match self.operation {
+            BinaryOperation::Concat => Type::String,
+            operation if operation.is_comparator() => Type::Boolean,
+            BinaryOperation::And | BinaryOperation::Or => {
+                let (left, right) = (self.left.infer(system), self.right.infer(system));
+                if left == right {
+                    left
+                } else {
+                    Type::Any
+                }
+            }
+            // anything else could be using a metamethod
+            _ => match (self.left.infer(system), self.right.infer(system)) {
+                (Type::Number, Type::Number) => Type::Number,
+                (Type::Vector, Type::Vector | Type::Number) | (Type::Number, Type::Vector) => {
+                    Type::Vector
+                }
+                _ => Type::Any,
+            },
+        }
+    }
+}
+
 impl<'a: 'b, 'b> Reduce for Binary {
     fn reduce(self) -> RValue {
         // TODO: true == true, true == false, etc.
@@ -1,6 +1,11 @@
 use std::fmt;
 
-use crate::{formatter::Formatter, has_side_effects, LocalRw, RcLocal, Traverse};
+use crate::{
+    formatter::Formatter,
+    has_side_effects,
+    type_system::{Infer, TypeSystem},
+    Index, Literal, LocalRw, RcLocal, Traverse, Type,
+};
 
 use super::RValue;
 
@@ -8,6 +13,7 @@ use super::RValue;
 pub struct Call {
     pub value: Box<RValue>,
     pub arguments: Vec<RValue>,
+    pub line: Option<usize>,
 }
 
 impl Call {
@@ -15,6 +21,7 @@ impl Call {
         Self {
             value: Box::new(value),
             arguments,
+            line: None,
         }
     }
 }
@@ -29,6 +36,35 @@ has_side_effects!(Call);
 //     }
 // }
 
+impl Infer for Call {
+    fn infer(&self, system: &TypeSystem) -> Type {
+        match &*self.value {
+            RValue::Global(global) => match global.0.as_slice() {
+                b"tostring" | b"type" | b"typeof" => Type::String,
+                b"tonumber" => Type::Optional(Box::new(Type::Number)),
+                _ => Type::Any,
+            },
+            RValue::Index(Index {
+                left: box RValue::Global(library),
+                right: box RValue::Literal(Literal::String(function)),
+            }) => match (library.0.as_slice(), function.as_slice()) {
+                (b"math", _) => Type::Number,
+                (b"string", b"format" | b"sub" | b"upper" | b"lower" | b"rep" | b"reverse") => {
+                    Type::String
+                }
+                (b"string", b"len") => Type::Number,
+                _ => Type::Any,
+            },
+            value => match value.infer(system) {
+                Type::Function(_, return_types) if return_types.len() == 1 => {
+                    return_types.into_iter().next().unwrap()
+                }
+                _ => Type::Any,
+            },
+        }
+    }
+}
+
 impl Traverse for Call {
     fn rvalues_mut(&mut self) -> Vec<&mut RValue> {
         std::iter::once(self.value.as_mut())
@@ -65,8 +101,10 @@ impl fmt::Display for Call {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         Formatter {
             indentation_level: 0,
-            indentation_mode: Default::default(),
+            options: Default::default(),
             output: f,
+            last_line: None,
+            position: None,
         }
         .format_call(self)
     }
@@ -78,6 +116,7 @@ pub struct MethodCall {
     pub value: Box<RValue>,
     pub method: String,
     pub arguments: Vec<RValue>,
+    pub line: Option<usize>,
 }
 
 impl MethodCall {
@@ -86,6 +125,7 @@ impl MethodCall {
             value: Box::new(value),
             method,
             arguments,
+            line: None,
         }
     }
 }
@@ -129,8 +169,10 @@ impl fmt::Display for MethodCall {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         Formatter {
             indentation_level: 0,
-            indentation_mode: Default::default(),
+            options: Default::default(),
             output: f,
+            last_line: None,
+            position: None,
         }
         .format_method_call(self)
     }
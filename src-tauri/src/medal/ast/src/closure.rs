@@ -20,7 +20,13 @@ pub enum Upvalue {
 pub struct Function {
     pub name: Option<String>,
     pub parameters: Vec<RcLocal>,
+    // empty if the function has no type info
+    pub parameter_types: Vec<Type>,
+    // empty if the types of the return values aren't known
+    pub return_types: Vec<Type>,
     pub is_variadic: bool,
+    /// Has the `@native` attribute
+    pub is_native: bool,
     pub body: Block,
 }
 
@@ -41,16 +47,19 @@ impl Reduce for Closure {
 }
 
 impl Infer for Closure {
-    fn infer<'a: 'b, 'b>(&'a mut self, _system: &mut TypeSystem<'b>) -> Type {
-        todo!()
-        // let return_values = system.analyze_block(&mut self.body);
-        // let parameters = self
-        //     .parameters
-        //     .iter_mut()
-        //     .map(|l| l.infer(system))
-        //     .collect_vec();
+    fn infer(&self, system: &TypeSystem) -> Type {
+        let function = self.function.lock();
+        let parameters = function
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, parameter)| match function.parameter_types.get(i) {
+                Some(r#type) if *r#type != Type::Any => r#type.clone(),
+                _ => parameter.infer(system),
+            })
+            .collect();
 
-        // Type::Function(parameters, return_values)
+        Type::Function(parameters, system.return_types(&function.body))
     }
 }
 
@@ -58,8 +67,10 @@ impl fmt::Display for Closure {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         Formatter {
             indentation_level: 0,
-            indentation_mode: Default::default(),
+            options: Default::default(),
             output: f,
+            last_line: None,
+            position: None,
         }
         .format_closure(self)
     }
@@ -0,0 +1,101 @@
+use std::fmt;
+
+use crate::{formatter::Formatter, BinaryOperation, LocalRw, RcLocal, SideEffects, Traverse};
+
+use super::{LValue, RValue};
+
+/// `left op= right`, only supported by Luau
+#[derive(Debug, Clone, PartialEq)]
+pub struct CompoundAssign {
+    pub left: LValue,
+    pub operation: BinaryOperation,
+    pub right: RValue,
+    pub line: Option<usize>,
+}
+
+impl CompoundAssign {
+    pub fn new(left: LValue, operation: BinaryOperation, right: RValue) -> Self {
+        Self {
+            left,
+            operation,
+            right,
+            line: None,
+        }
+    }
+
+    pub fn is_supported(operation: BinaryOperation) -> bool {
+        matches!(
+            operation,
+            BinaryOperation::Add
+                | BinaryOperation::Sub
+                | BinaryOperation::Mul
+                | BinaryOperation::Div
+                | BinaryOperation::IDiv
+                | BinaryOperation::Mod
+                | BinaryOperation::Pow
+                | BinaryOperation::Concat
+        )
+    }
+}
+
+impl Traverse for CompoundAssign {
+    fn lvalues_mut(&mut self) -> Vec<&mut LValue> {
+        vec![&mut self.left]
+    }
+
+    fn rvalues_mut(&mut self) -> Vec<&mut RValue> {
+        vec![&mut self.right]
+    }
+
+    fn rvalues(&self) -> Vec<&RValue> {
+        vec![&self.right]
+    }
+}
+
+impl SideEffects for CompoundAssign {
+    fn has_side_effects(&self) -> bool {
+        self.right.has_side_effects() || self.left.has_side_effects()
+    }
+}
+
+// the target is read as well as written
+impl LocalRw for CompoundAssign {
+    fn values_read(&self) -> Vec<&RcLocal> {
+        self.left
+            .values_read()
+            .into_iter()
+            .chain(self.left.as_local())
+            .chain(self.right.values_read())
+            .collect()
+    }
+
+    fn values_read_mut(&mut self) -> Vec<&mut RcLocal> {
+        // a local target can't be borrowed mutably twice, so it's only returned as written
+        self.left
+            .values_read_mut()
+            .into_iter()
+            .chain(self.right.values_read_mut())
+            .collect()
+    }
+
+    fn values_written(&self) -> Vec<&RcLocal> {
+        self.left.values_written()
+    }
+
+    fn values_written_mut(&mut self) -> Vec<&mut RcLocal> {
+        self.left.values_written_mut()
+    }
+}
+
+impl fmt::Display for CompoundAssign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Formatter {
+            indentation_level: 0,
+            options: Default::default(),
+            output: f,
+            last_line: None,
+            position: None,
+        }
+        .format_compound_assign(self)
+    }
+}
@@ -0,0 +1,83 @@
+use itertools::Either;
+
+use crate::{Assign, Block, CompoundAssign, LValue, RValue, Statement, Traverse};
+
+// whether evaluating `rvalue` twice is the same as evaluating it once,
+// assuming __index doesn't have side effects
+fn is_repeatable(rvalue: &RValue) -> bool {
+    match rvalue {
+        RValue::Local(_) | RValue::Global(_) | RValue::Literal(_) => true,
+        RValue::Index(index) => is_repeatable(&index.left) && is_repeatable(&index.right),
+        _ => false,
+    }
+}
+
+fn is_same_target(lvalue: &LValue, rvalue: &RValue) -> bool {
+    match (lvalue, rvalue) {
+        (LValue::Local(left), RValue::Local(right)) => left == right,
+        (LValue::Global(left), RValue::Global(right)) => left == right,
+        (LValue::Index(left), RValue::Index(right)) => {
+            left == right && is_repeatable(&left.left) && is_repeatable(&left.right)
+        }
+        _ => false,
+    }
+}
+
+fn fold(assign: &mut Assign) -> Option<CompoundAssign> {
+    if assign.prefix || assign.left.len() != 1 || assign.right.len() != 1 {
+        return None;
+    }
+    let RValue::Binary(binary) = &mut assign.right[0] else {
+        return None;
+    };
+    if !CompoundAssign::is_supported(binary.operation)
+        || !is_same_target(&assign.left[0], &binary.left)
+    {
+        return None;
+    }
+    let right = std::mem::replace(&mut *binary.right, RValue::Literal(crate::Literal::Nil));
+    let mut compound_assign =
+        CompoundAssign::new(assign.left.pop().unwrap(), binary.operation, right);
+    compound_assign.line = assign.line;
+    Some(compound_assign)
+}
+
+/// Turns `a = a op b` into `a op= b` where `a` is a local, global or an index
+/// that can be evaluated twice
+pub fn fold_compound_assignments(block: &mut Block) {
+    for statement in &mut block.0 {
+        statement.post_traverse_values(&mut |value| -> Option<()> {
+            if let Either::Right(RValue::Closure(closure)) = value {
+                fold_compound_assignments(&mut closure.function.lock().body)
+            };
+            None
+        });
+        match statement {
+            Statement::Assign(assign) => {
+                if let Some(compound_assign) = fold(assign) {
+                    *statement = compound_assign.into();
+                }
+            }
+            Statement::If(r#if) => {
+                fold_compound_assignments(&mut r#if.then_block.lock());
+                fold_compound_assignments(&mut r#if.else_block.lock());
+            }
+            Statement::While(r#while) => {
+                fold_compound_assignments(&mut r#while.block.lock());
+            }
+            Statement::Do(r#do) => {
+                fold_compound_assignments(&mut r#do.block.lock());
+            }
+            Statement::Repeat(repeat) => {
+                fold_compound_assignments(&mut repeat.block.lock());
+            }
+            Statement::NumericFor(numeric_for) => {
+                fold_compound_assignments(&mut numeric_for.block.lock());
+            }
+            Statement::GenericFor(generic_for) => {
+                fold_compound_assignments(&mut generic_for.block.lock());
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,491 @@
+//! A plain, serializable mirror of the AST for tools that want to analyze decompiled
+//! code without parsing the text output.
+//!
+//! Locals are identified by an `id` that is assigned in the order they're first
+//! encountered, so the same bytecode always exports the same ids. Upvalues of a closure
+//! refer to the ids of the locals they capture.
+
+use rustc_hash::FxHashMap;
+use serde::Serialize;
+
+use crate as ast;
+
+#[derive(Debug, Serialize)]
+pub struct Chunk {
+    /// Every local in the chunk, indexed by id
+    pub locals: Vec<Local>,
+    pub body: Vec<Statement>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Local {
+    pub id: usize,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Function {
+    pub name: Option<String>,
+    pub parameters: Vec<Local>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub parameter_types: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub return_types: Vec<String>,
+    pub is_variadic: bool,
+    pub is_native: bool,
+    pub body: Vec<Statement>,
+}
+
+/// Lua strings are bytes, the ones that aren't valid UTF-8 are exported as an array of them
+/// so that different strings never export the same
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum Bytes {
+    Utf8(String),
+    Raw(Vec<u8>),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", content = "local", rename_all = "snake_case")]
+pub enum Upvalue {
+    Copy(Local),
+    Ref(Local),
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum Literal {
+    Nil,
+    Boolean(bool),
+    /// `NaN` and infinities serialize as `null` in JSON
+    Number(f64),
+    String(Bytes),
+    Vector([f32; 3]),
+}
+
+#[derive(Debug, Serialize)]
+pub struct TableField {
+    /// `None` for array items
+    pub key: Option<Expression>,
+    pub value: Expression,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Expression {
+    Local(Local),
+    Global {
+        name: Bytes,
+    },
+    Call {
+        value: Box<Expression>,
+        arguments: Vec<Expression>,
+    },
+    MethodCall {
+        value: Box<Expression>,
+        method: String,
+        arguments: Vec<Expression>,
+    },
+    VarArg,
+    Table {
+        fields: Vec<TableField>,
+    },
+    Literal(Literal),
+    Index {
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Unary {
+        operator: String,
+        value: Box<Expression>,
+    },
+    Binary {
+        operator: String,
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+    Closure {
+        function: Box<Function>,
+        upvalues: Vec<Upvalue>,
+    },
+    /// A call or vararg that is truncated to its first value
+    Select {
+        value: Box<Expression>,
+    },
+    IfExpression {
+        condition: Box<Expression>,
+        then_value: Box<Expression>,
+        else_value: Box<Expression>,
+    },
+    InterpolatedString {
+        strings: Vec<Bytes>,
+        values: Vec<Expression>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Target {
+    Local(Local),
+    Global {
+        name: Bytes,
+    },
+    Index {
+        left: Box<Expression>,
+        right: Box<Expression>,
+    },
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Statement {
+    Empty,
+    Call {
+        value: Expression,
+        arguments: Vec<Expression>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+    },
+    MethodCall {
+        value: Expression,
+        method: String,
+        arguments: Vec<Expression>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+    },
+    Assign {
+        left: Vec<Target>,
+        right: Vec<Expression>,
+        /// Whether this is a `local` declaration
+        prefix: bool,
+        #[serde(skip_serializing_if = "Vec::is_empty")]
+        types: Vec<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+    },
+    CompoundAssign {
+        operator: String,
+        left: Target,
+        right: Expression,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+    },
+    If {
+        condition: Expression,
+        then_block: Vec<Statement>,
+        else_block: Vec<Statement>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+    },
+    Do {
+        block: Vec<Statement>,
+    },
+    Goto {
+        label: String,
+    },
+    Label {
+        label: String,
+    },
+    While {
+        condition: Expression,
+        block: Vec<Statement>,
+    },
+    Repeat {
+        block: Vec<Statement>,
+        condition: Expression,
+    },
+    NumericFor {
+        counter: Local,
+        initial: Expression,
+        limit: Expression,
+        step: Expression,
+        block: Vec<Statement>,
+    },
+    GenericFor {
+        locals: Vec<Local>,
+        right: Vec<Expression>,
+        block: Vec<Statement>,
+    },
+    Return {
+        values: Vec<Expression>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        line: Option<usize>,
+    },
+    Continue,
+    Break,
+    Close {
+        locals: Vec<Local>,
+    },
+    SetList {
+        table: Local,
+        index: usize,
+        values: Vec<Expression>,
+        tail: Option<Expression>,
+    },
+    Comment {
+        text: String,
+    },
+    /// Statements that only exist before loops are structured
+    Unstructured {
+        text: String,
+    },
+}
+
+pub fn export(block: &ast::Block) -> Chunk {
+    let mut exporter = Exporter::default();
+    let body = exporter.block(block);
+    Chunk {
+        locals: exporter.locals,
+        body,
+    }
+}
+
+#[derive(Default)]
+struct Exporter {
+    ids: FxHashMap<ast::RcLocal, usize>,
+    locals: Vec<Local>,
+}
+
+impl Exporter {
+    fn local(&mut self, local: &ast::RcLocal) -> Local {
+        let id = *self.ids.entry(local.clone()).or_insert_with(|| {
+            self.locals.push(Local {
+                id: self.locals.len(),
+                name: local.0.lock().0.clone(),
+            });
+            self.locals.len() - 1
+        });
+        self.locals[id].clone()
+    }
+
+    fn string(string: &[u8]) -> Bytes {
+        match std::str::from_utf8(string) {
+            Ok(string) => Bytes::Utf8(string.to_string()),
+            Err(_) => Bytes::Raw(string.to_vec()),
+        }
+    }
+
+    fn types(types: &[ast::Type]) -> Vec<String> {
+        types.iter().map(ToString::to_string).collect()
+    }
+
+    fn block(&mut self, block: &ast::Block) -> Vec<Statement> {
+        block.iter().map(|s| self.statement(s)).collect()
+    }
+
+    fn expressions(&mut self, rvalues: &[ast::RValue]) -> Vec<Expression> {
+        rvalues.iter().map(|r| self.expression(r)).collect()
+    }
+
+    fn boxed(&mut self, rvalue: &ast::RValue) -> Box<Expression> {
+        Box::new(self.expression(rvalue))
+    }
+
+    fn function(&mut self, function: &ast::Function) -> Function {
+        Function {
+            name: function.name.clone(),
+            parameters: function.parameters.iter().map(|p| self.local(p)).collect(),
+            parameter_types: Self::types(&function.parameter_types),
+            return_types: Self::types(&function.return_types),
+            is_variadic: function.is_variadic,
+            is_native: function.is_native,
+            body: self.block(&function.body),
+        }
+    }
+
+    fn call(&mut self, call: &ast::Call) -> Expression {
+        Expression::Call {
+            value: self.boxed(&call.value),
+            arguments: self.expressions(&call.arguments),
+        }
+    }
+
+    fn method_call(&mut self, method_call: &ast::MethodCall) -> Expression {
+        Expression::MethodCall {
+            value: self.boxed(&method_call.value),
+            method: method_call.method.clone(),
+            arguments: self.expressions(&method_call.arguments),
+        }
+    }
+
+    fn expression(&mut self, rvalue: &ast::RValue) -> Expression {
+        match rvalue {
+            ast::RValue::Local(local) => Expression::Local(self.local(local)),
+            ast::RValue::Global(global) => Expression::Global {
+                name: Self::string(&global.0),
+            },
+            ast::RValue::Call(call) => self.call(call),
+            ast::RValue::MethodCall(method_call) => self.method_call(method_call),
+            ast::RValue::VarArg(_) => Expression::VarArg,
+            ast::RValue::Table(table) => Expression::Table {
+                fields: table
+                    .0
+                    .iter()
+                    .map(|(key, value)| TableField {
+                        key: key.as_ref().map(|k| self.expression(k)),
+                        value: self.expression(value),
+                    })
+                    .collect(),
+            },
+            ast::RValue::Literal(literal) => Expression::Literal(match literal {
+                ast::Literal::Nil => Literal::Nil,
+                ast::Literal::Boolean(value) => Literal::Boolean(*value),
+                ast::Literal::Number(value) => Literal::Number(*value),
+                ast::Literal::String(value) => Literal::String(Self::string(value)),
+                ast::Literal::Vector(x, y, z) => Literal::Vector([*x, *y, *z]),
+            }),
+            ast::RValue::Index(index) => Expression::Index {
+                left: self.boxed(&index.left),
+                right: self.boxed(&index.right),
+            },
+            ast::RValue::Unary(unary) => Expression::Unary {
+                operator: unary.operation.to_string().trim_end().to_string(),
+                value: self.boxed(&unary.value),
+            },
+            ast::RValue::Binary(binary) => Expression::Binary {
+                operator: binary.operation.to_string(),
+                left: self.boxed(&binary.left),
+                right: self.boxed(&binary.right),
+            },
+            ast::RValue::Closure(closure) => Expression::Closure {
+                function: Box::new(self.function(&closure.function.lock())),
+                upvalues: closure
+                    .upvalues
+                    .iter()
+                    .map(|upvalue| match upvalue {
+                        ast::Upvalue::Copy(local) => Upvalue::Copy(self.local(local)),
+                        ast::Upvalue::Ref(local) => Upvalue::Ref(self.local(local)),
+                    })
+                    .collect(),
+            },
+            ast::RValue::Select(select) => Expression::Select {
+                value: Box::new(match select {
+                    ast::Select::VarArg(_) => Expression::VarArg,
+                    ast::Select::Call(call) => self.call(call),
+                    ast::Select::MethodCall(method_call) => self.method_call(method_call),
+                }),
+            },
+            ast::RValue::IfExpression(if_expression) => Expression::IfExpression {
+                condition: self.boxed(&if_expression.condition),
+                then_value: self.boxed(&if_expression.then_value),
+                else_value: self.boxed(&if_expression.else_value),
+            },
+            ast::RValue::InterpolatedString(interpolated_string) => {
+                Expression::InterpolatedString {
+                    strings: interpolated_string
+                        .strings
+                        .iter()
+                        .map(|s| Self::string(s))
+                        .collect(),
+                    values: self.expressions(&interpolated_string.values),
+                }
+            }
+        }
+    }
+
+    fn target(&mut self, lvalue: &ast::LValue) -> Target {
+        match lvalue {
+            ast::LValue::Local(local) => Target::Local(self.local(local)),
+            ast::LValue::Global(global) => Target::Global {
+                name: Self::string(&global.0),
+            },
+            ast::LValue::Index(index) => Target::Index {
+                left: self.boxed(&index.left),
+                right: self.boxed(&index.right),
+            },
+        }
+    }
+
+    fn statement(&mut self, statement: &ast::Statement) -> Statement {
+        match statement {
+            ast::Statement::Empty(_) => Statement::Empty,
+            ast::Statement::Call(call) => Statement::Call {
+                value: self.expression(&call.value),
+                arguments: self.expressions(&call.arguments),
+                line: call.line,
+            },
+            ast::Statement::MethodCall(method_call) => Statement::MethodCall {
+                value: self.expression(&method_call.value),
+                method: method_call.method.clone(),
+                arguments: self.expressions(&method_call.arguments),
+                line: method_call.line,
+            },
+            ast::Statement::Assign(assign) => Statement::Assign {
+                left: assign.left.iter().map(|l| self.target(l)).collect(),
+                right: self.expressions(&assign.right),
+                prefix: assign.prefix,
+                types: Self::types(&assign.types),
+                line: assign.line,
+            },
+            ast::Statement::CompoundAssign(compound_assign) => Statement::CompoundAssign {
+                operator: compound_assign.operation.to_string(),
+                left: self.target(&compound_assign.left),
+                right: self.expression(&compound_assign.right),
+                line: compound_assign.line,
+            },
+            ast::Statement::If(r#if) => Statement::If {
+                condition: self.expression(&r#if.condition),
+                then_block: self.block(&r#if.then_block.lock()),
+                else_block: self.block(&r#if.else_block.lock()),
+                line: r#if.line,
+            },
+            ast::Statement::Do(r#do) => Statement::Do {
+                block: self.block(&r#do.block.lock()),
+            },
+            ast::Statement::Goto(goto) => Statement::Goto {
+                label: goto.0 .0.clone(),
+            },
+            ast::Statement::Label(label) => Statement::Label {
+                label: label.0.clone(),
+            },
+            ast::Statement::While(r#while) => Statement::While {
+                condition: self.expression(&r#while.condition),
+                block: self.block(&r#while.block.lock()),
+            },
+            ast::Statement::Repeat(repeat) => Statement::Repeat {
+                block: self.block(&repeat.block.lock()),
+                condition: self.expression(&repeat.condition),
+            },
+            ast::Statement::NumericFor(numeric_for) => Statement::NumericFor {
+                counter: self.local(&numeric_for.counter),
+                initial: self.expression(&numeric_for.initial),
+                limit: self.expression(&numeric_for.limit),
+                step: self.expression(&numeric_for.step),
+                block: self.block(&numeric_for.block.lock()),
+            },
+            ast::Statement::GenericFor(generic_for) => Statement::GenericFor {
+                locals: generic_for
+                    .res_locals
+                    .iter()
+                    .map(|l| self.local(l))
+                    .collect(),
+                right: self.expressions(&generic_for.right),
+                block: self.block(&generic_for.block.lock()),
+            },
+            ast::Statement::Return(r#return) => Statement::Return {
+                values: self.expressions(&r#return.values),
+                line: r#return.line,
+            },
+            ast::Statement::Continue(_) => Statement::Continue,
+            ast::Statement::Break(_) => Statement::Break,
+            ast::Statement::Close(close) => Statement::Close {
+                locals: close.locals.iter().map(|l| self.local(l)).collect(),
+            },
+            ast::Statement::SetList(set_list) => Statement::SetList {
+                table: self.local(&set_list.object_local),
+                index: set_list.index,
+                values: self.expressions(&set_list.values),
+                tail: set_list.tail.as_ref().map(|t| self.expression(t)),
+            },
+            ast::Statement::Comment(comment) => Statement::Comment {
+                text: comment.text.clone(),
+            },
+            ast::Statement::NumForInit(_)
+            | ast::Statement::NumForNext(_)
+            | ast::Statement::GenericForInit(_)
+            | ast::Statement::GenericForNext(_) => Statement::Unstructured {
+                text: statement.to_string(),
+            },
+        }
+    }
+}
@@ -1,16 +1,19 @@
 use std::iter;
 use std::{
     borrow::Cow,
+    cell::Cell,
     fmt::{self},
 };
 
 use itertools::Itertools;
 
 use crate::{
-    Assign, Binary, BinaryOperation, Block, Call, Closure, GenericFor, If, Index, LValue, Literal,
-    MethodCall, NumericFor, RValue, Repeat, Return, Select, Statement, Table, Unary, While,
+    Assign, Binary, BinaryOperation, Block, Call, Closure, CompoundAssign, Do, GenericFor, If,
+    IfExpression, Index, InterpolatedString, LValue, Literal, MethodCall, NumericFor, RValue,
+    Repeat, Return, Select, Statement, Table, Type, Unary, While,
 };
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum IndentationMode {
     Spaces(u8),
     Tab,
@@ -41,6 +44,84 @@ impl Default for IndentationMode {
     }
 }
 
+/// How the formatter uses the source lines attached to statements
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LineMode {
+    #[default]
+    Ignore,
+    /// Print a `-- line N` comment before statements that start a new source line
+    Comment,
+    /// Insert blank lines so statements are printed close to their source line
+    Layout,
+}
+
+/// Which quotes string literals are printed with
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum QuoteStyle {
+    #[default]
+    Double,
+    Single,
+}
+
+impl QuoteStyle {
+    fn quote(self) -> u8 {
+        match self {
+            Self::Double => b'"',
+            Self::Single => b'\'',
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct FormatOptions {
+    pub indentation_mode: IndentationMode,
+    pub line_mode: LineMode,
+    /// Calls and tables that would go past this column are split over multiple lines
+    pub max_width: Option<usize>,
+    pub quote_style: QuoteStyle,
+    /// Print integer masks passed to `bit32` functions in hexadecimal, other numbers
+    /// are always printed in the shortest form that round-trips
+    pub hex_bit_masks: bool,
+    /// Put a comma after the last field of tables that span multiple lines
+    pub trailing_commas: bool,
+    /// Blank lines printed around top-level functions
+    pub blank_lines_between_functions: usize,
+}
+
+// the number of columns a tab is counted as when wrapping
+const TAB_WIDTH: usize = 4;
+
+// where the formatter is in the output, used by `LineMode::Layout` and `max_width`
+#[derive(Default)]
+pub(crate) struct Position {
+    lines: Cell<usize>,
+    column: Cell<usize>,
+}
+
+struct PositionCounter<'a, W: fmt::Write> {
+    output: &'a mut W,
+    position: &'a Position,
+}
+
+impl<W: fmt::Write> fmt::Write for PositionCounter<'_, W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '\n' => {
+                    self.position.lines.set(self.position.lines.get() + 1);
+                    self.position.column.set(0);
+                }
+                '\t' => self
+                    .position
+                    .column
+                    .set(self.position.column.get() + TAB_WIDTH),
+                _ => self.position.column.set(self.position.column.get() + 1),
+            }
+        }
+        self.output.write_str(s)
+    }
+}
+
 pub(crate) fn format_arg_list(list: &[RValue]) -> String {
     let mut s = String::new();
     for (index, rvalue) in list.iter().enumerate() {
@@ -59,8 +140,12 @@ pub(crate) fn format_arg_list(list: &[RValue]) -> String {
 
 pub struct Formatter<'a, W: fmt::Write> {
     pub(crate) indentation_level: usize,
-    pub(crate) indentation_mode: IndentationMode,
+    pub(crate) options: FormatOptions,
     pub(crate) output: &'a mut W,
+    // the source line of the last statement that was annotated
+    pub(crate) last_line: Option<usize>,
+    // where `output` is at, if it's being tracked
+    pub(crate) position: Option<&'a Position>,
 }
 
 impl<'a, W: fmt::Write> Formatter<'a, W> {
@@ -71,17 +156,77 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
     ) -> fmt::Result {
         let mut formatter = Self {
             indentation_level: 0,
-            indentation_mode,
+            options: FormatOptions {
+                indentation_mode,
+                ..Default::default()
+            },
             output,
+            last_line: None,
+            position: None,
+        };
+        formatter.format_block_no_indent(main)
+    }
+
+    pub fn format_with_options(
+        main: &Block,
+        output: &'a mut W,
+        options: FormatOptions,
+    ) -> fmt::Result {
+        let position = Position::default();
+        let mut output = PositionCounter {
+            output,
+            position: &position,
+        };
+        let mut formatter = Formatter {
+            indentation_level: 0,
+            options,
+            output: &mut output,
+            last_line: None,
+            position: Some(&position),
         };
         formatter.format_block_no_indent(main)
     }
 
     fn indent(&mut self) -> fmt::Result {
-        self.indentation_mode
+        self.options
+            .indentation_mode
             .display(&mut self.output, self.indentation_level)
     }
 
+    // the width of what `format` prints, if it fits on one line
+    fn flat_width(
+        &self,
+        format: impl FnOnce(&mut Formatter<'_, String>) -> fmt::Result,
+    ) -> Option<usize> {
+        let mut output = String::new();
+        let mut formatter = Formatter {
+            indentation_level: self.indentation_level,
+            options: FormatOptions {
+                line_mode: LineMode::Ignore,
+                max_width: None,
+                ..self.options
+            },
+            output: &mut output,
+            last_line: None,
+            position: None,
+        };
+        format(&mut formatter).ok()?;
+        (!output.contains('\n')).then(|| output.chars().count())
+    }
+
+    // whether printing `width` more columns on the current line would go past `max_width`
+    fn overflows(&self, width: usize) -> bool {
+        match (self.options.max_width, self.position) {
+            (Some(max_width), Some(position)) => position.column.get() + width > max_width,
+            _ => false,
+        }
+    }
+
+    fn is_function_declaration(statement: &Statement) -> bool {
+        matches!(statement, Statement::Assign(assign)
+            if assign.left.len() == 1 && matches!(assign.right[..], [RValue::Closure(_)]))
+    }
+
     // (function() end)()
     // (function() end)[1]
     fn should_wrap_left_rvalue(value: &RValue) -> bool {
@@ -105,7 +250,16 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         for (i, statement) in block.iter().enumerate() {
             if i != 0 {
                 writeln!(self.output)?;
+                if self.indentation_level == 0
+                    && (Self::is_function_declaration(statement)
+                        || Self::is_function_declaration(&block[i - 1]))
+                {
+                    for _ in 0..self.options.blank_lines_between_functions {
+                        writeln!(self.output)?;
+                    }
+                }
             }
+            self.format_line(statement)?;
             self.format_statement(statement)?;
             if let Some(next_statement) =
                 block.iter().skip(i + 1).find(|s| s.as_comment().is_none())
@@ -119,6 +273,9 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                         | RValue::MethodCall(_)
                         | RValue::Select(Select::Call(_) | Select::MethodCall(_)) => true,
                         RValue::Binary(binary) => is_ambiguous(&binary.right),
+                        RValue::IfExpression(if_expression) => {
+                            is_ambiguous(&if_expression.else_value)
+                        }
                         _ => false,
                     }
                 }
@@ -126,8 +283,11 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                 let disambiguate = match statement {
                     Statement::Call(_) | Statement::MethodCall(_) => true,
                     Statement::Repeat(repeat) => is_ambiguous(&repeat.condition),
+                    Statement::CompoundAssign(compound_assign) => {
+                        is_ambiguous(&compound_assign.right)
+                    }
                     Statement::Assign(Assign { right: list, .. })
-                    | Statement::Return(Return { values: list }) => {
+                    | Statement::Return(Return { values: list, .. }) => {
                         if let Some(last) = list.last() {
                             is_ambiguous(last)
                         } else {
@@ -150,6 +310,10 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                                 false
                             }
                         }
+                        Statement::CompoundAssign(CompoundAssign {
+                            left: LValue::Index(index),
+                            ..
+                        }) => Self::should_wrap_left_rvalue(&index.left),
                         Statement::Call(Call { value, .. })
                         | Statement::MethodCall(MethodCall { value, .. }) => {
                             Self::should_wrap_left_rvalue(value)
@@ -165,6 +329,32 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         Ok(())
     }
 
+    // must be called at the start of a line
+    fn format_line(&mut self, statement: &Statement) -> fmt::Result {
+        let Some(line) = statement.line() else {
+            return Ok(());
+        };
+        match self.options.line_mode {
+            LineMode::Ignore => {}
+            LineMode::Comment => {
+                if self.last_line != Some(line) {
+                    self.indent()?;
+                    writeln!(self.output, "-- line {}", line)?;
+                }
+            }
+            LineMode::Layout => {
+                if let Some(position) = self.position {
+                    // the statement will be printed on line `lines + 1`
+                    for _ in position.lines.get() + 1..line {
+                        writeln!(self.output)?;
+                    }
+                }
+            }
+        }
+        self.last_line = Some(line);
+        Ok(())
+    }
+
     fn format_lvalue(&mut self, lvalue: &LValue) -> fmt::Result {
         match lvalue {
             LValue::Index(index) => self.format_index(index),
@@ -201,7 +391,12 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         let sequential_keys = Self::are_table_keys_sequential(table);
         let should_space = !table.0.is_empty();
         let should_format = !table.0.is_empty() && (!sequential_keys || table.0.len() > 3)
-            || Self::contains_table(table);
+            || Self::contains_table(table)
+            || (should_space
+                && self.options.max_width.is_some()
+                && self
+                    .flat_width(|f| f.format_table(table))
+                    .is_some_and(|width| self.overflows(width)));
         write!(self.output, "{{")?;
         if should_format {
             writeln!(self.output)?;
@@ -237,6 +432,9 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                     write!(self.output, "{}", if should_format { "\n" } else { " " })?;
                 }
             }
+            if is_last && should_format && self.options.trailing_commas {
+                write!(self.output, ",")?;
+            }
         }
         self.indentation_level -= 1;
         if should_format {
@@ -261,41 +459,65 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         Ok(())
     }
 
-    pub(crate) fn format_binary(&mut self, binary: &Binary) -> fmt::Result {
-        let parentheses = |f: &mut Self, wrap: bool, rvalue: &RValue| -> fmt::Result {
-            if wrap {
-                write!(f.output, "(")?;
-            }
-            f.format_rvalue(rvalue)?;
-            if wrap {
-                write!(f.output, ")")?;
-            }
-            Ok(())
-        };
+    fn format_grouped(&mut self, wrap: bool, rvalue: &RValue) -> fmt::Result {
+        if wrap {
+            write!(self.output, "(")?;
+        }
+        self.format_rvalue(rvalue)?;
+        if wrap {
+            write!(self.output, ")")?;
+        }
+        Ok(())
+    }
 
-        parentheses(self, binary.left_group(), &binary.left)?;
-        write!(self.output, " {} ", binary.operation)?;
-        parentheses(self, binary.right_group(), &binary.right)
+    pub(crate) fn format_binary(&mut self, binary: &Binary) -> fmt::Result {
+        self.format_grouped(binary.left_group(), &binary.left)?;
+        // long chains continue on the next line, starting with the operator
+        let operator = format!(" {} ", binary.operation);
+        if self.options.max_width.is_some()
+            && self
+                .flat_width(|f| f.format_grouped(binary.right_group(), &binary.right))
+                .is_some_and(|width| self.overflows(operator.len() + width))
+        {
+            writeln!(self.output)?;
+            self.indentation_level += 1;
+            self.indent()?;
+            self.indentation_level -= 1;
+            write!(self.output, "{}", operator.trim_start())?;
+        } else {
+            write!(self.output, "{}", operator)?;
+        }
+        self.format_grouped(binary.right_group(), &binary.right)
     }
 
     fn format_closure_parameters(&mut self, closure: &Closure) -> fmt::Result {
         let function = closure.function.lock();
+        let mut parameters = function.parameters.iter().enumerate().map(|(i, x)| {
+            match function.parameter_types.get(i) {
+                Some(r#type) if *r#type != Type::Any => format!("{}: {}", x, r#type),
+                _ => x.to_string(),
+            }
+        });
         write!(
             self.output,
             "{}",
             if function.is_variadic {
-                function
-                    .parameters
-                    .iter()
-                    .map(|x| x.to_string())
-                    .chain(std::iter::once("...".into()))
-                    .join(", ")
+                parameters.chain(iter::once("...".into())).join(", ")
             } else {
-                function.parameters.iter().join(", ")
+                parameters.join(", ")
             }
         )
     }
 
+    fn format_return_types(&mut self, closure: &Closure) -> fmt::Result {
+        let function = closure.function.lock();
+        match function.return_types.as_slice() {
+            [] => Ok(()),
+            [r#type] if r#type.precedence() == 0 => write!(self.output, ": {}", r#type),
+            types => write!(self.output, ": ({})", types.iter().join(", ")),
+        }
+    }
+
     fn format_closure_body(&mut self, closure: &Closure) -> fmt::Result {
         let function = closure.function.lock();
         if !function.body.is_empty() {
@@ -338,22 +560,104 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         }
     }
 
+    fn format_attributes(&mut self, closure: &Closure) -> fmt::Result {
+        if closure.function.lock().is_native {
+            write!(self.output, "@native ")?;
+        }
+        Ok(())
+    }
+
     pub(crate) fn format_closure(&mut self, closure: &Closure) -> fmt::Result {
+        self.format_attributes(closure)?;
         write!(self.output, "function(")?;
         self.format_closure_parameters(closure)?;
         write!(self.output, ")")?;
+        self.format_return_types(closure)?;
         self.format_closure_body(closure)?;
         write!(self.output, "end")
     }
 
-    fn format_named_function(&mut self, name: &LValue, closure: &Closure) -> fmt::Result {
+    fn format_named_function(
+        &mut self,
+        is_local: bool,
+        name: &LValue,
+        closure: &Closure,
+    ) -> fmt::Result {
+        self.format_attributes(closure)?;
+        if is_local {
+            write!(self.output, "local ")?;
+        }
         write!(self.output, "function {}(", name)?;
         self.format_closure_parameters(closure)?;
         write!(self.output, ")")?;
+        self.format_return_types(closure)?;
         self.format_closure_body(closure)?;
         write!(self.output, "end")
     }
 
+    pub(crate) fn format_compound_assign(
+        &mut self,
+        compound_assign: &CompoundAssign,
+    ) -> fmt::Result {
+        self.format_lvalue(&compound_assign.left)?;
+        write!(self.output, " {}= ", compound_assign.operation)?;
+        self.format_rvalue(&compound_assign.right)
+    }
+
+    pub(crate) fn format_if_expression(&mut self, if_expression: &IfExpression) -> fmt::Result {
+        // a nested if expression would swallow the `then` or `else` that follows it
+        let parentheses = |f: &mut Self, rvalue: &RValue| -> fmt::Result {
+            let wrap = matches!(rvalue, RValue::IfExpression(_));
+            if wrap {
+                write!(f.output, "(")?;
+            }
+            f.format_rvalue(rvalue)?;
+            if wrap {
+                write!(f.output, ")")?;
+            }
+            Ok(())
+        };
+
+        write!(self.output, "if ")?;
+        parentheses(self, &if_expression.condition)?;
+        write!(self.output, " then ")?;
+        parentheses(self, &if_expression.then_value)?;
+        let mut else_value = &*if_expression.else_value;
+        while let RValue::IfExpression(if_expression) = else_value {
+            write!(self.output, " elseif ")?;
+            parentheses(self, &if_expression.condition)?;
+            write!(self.output, " then ")?;
+            parentheses(self, &if_expression.then_value)?;
+            else_value = &if_expression.else_value;
+        }
+        write!(self.output, " else ")?;
+        self.format_rvalue(else_value)
+    }
+
+    pub(crate) fn format_interpolated_string(
+        &mut self,
+        interpolated_string: &InterpolatedString,
+    ) -> fmt::Result {
+        write!(self.output, "`")?;
+        for (i, string) in interpolated_string.strings.iter().enumerate() {
+            write!(self.output, "{}", Self::escape_interpolated_string(string))?;
+            if let Some(value) = interpolated_string.values.get(i) {
+                // `{{` is rejected by the lexer
+                let wrap = matches!(value, RValue::Table(_));
+                write!(self.output, "{{")?;
+                if wrap {
+                    write!(self.output, "(")?;
+                }
+                self.format_rvalue(value)?;
+                if wrap {
+                    write!(self.output, ")")?;
+                }
+                write!(self.output, "}}")?;
+            }
+        }
+        write!(self.output, "`")
+    }
+
     fn format_rvalue(&mut self, rvalue: &RValue) -> fmt::Result {
         match rvalue {
             RValue::Select(Select::Call(call)) | RValue::Call(call) => self.format_call(call),
@@ -365,6 +669,20 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             RValue::Unary(unary) => self.format_unary(unary),
             RValue::Binary(binary) => self.format_binary(binary),
             RValue::Closure(closure) => self.format_closure(closure),
+            RValue::IfExpression(if_expression) => self.format_if_expression(if_expression),
+            RValue::InterpolatedString(interpolated_string) => {
+                self.format_interpolated_string(interpolated_string)
+            }
+            RValue::Literal(Literal::String(string)) => {
+                let quote = self.options.quote_style.quote() as char;
+                write!(
+                    self.output,
+                    "{}{}{}",
+                    quote,
+                    Self::escape_string(string, quote as u8),
+                    quote
+                )
+            }
             RValue::Literal(Literal::Number(n)) if n.is_infinite() => {
                 // TODO: only insert parentheses when necessary
                 write!(self.output, "(")?;
@@ -391,24 +709,77 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         }
     }
 
-    fn format_arg_list(&mut self, list: &[RValue]) -> fmt::Result {
-        for (index, rvalue) in list.iter().enumerate() {
-            if index + 1 == list.len() {
-                let wrap = matches!(rvalue, RValue::Select(_));
-                if wrap {
-                    write!(self.output, "(")?;
-                }
-                self.format_rvalue(rvalue)?;
-                if wrap {
-                    write!(self.output, ")")?;
-                }
-            } else {
+    // bit32 functions whose arguments are usually masks
+    fn takes_bit_masks(value: &RValue) -> bool {
+        if let RValue::Index(index) = value
+            && let RValue::Global(global) = &*index.left
+            && global.0 == b"bit32"
+            && let RValue::Literal(Literal::String(function)) = &*index.right
+        {
+            matches!(
+                function.as_slice(),
+                b"band" | b"bor" | b"bxor" | b"btest" | b"bnot"
+            )
+        } else {
+            false
+        }
+    }
+
+    fn format_argument(&mut self, rvalue: &RValue, is_last: bool, is_mask: bool) -> fmt::Result {
+        match rvalue {
+            // single digits read the same in decimal
+            &RValue::Literal(Literal::Number(n))
+                if is_mask && n.fract() == 0.0 && (10.0..=u32::MAX as f64).contains(&n) =>
+            {
+                write!(self.output, "{:#X}", n as u32)
+            }
+            RValue::Select(_) if is_last => {
+                write!(self.output, "(")?;
                 self.format_rvalue(rvalue)?;
+                write!(self.output, ")")
+            }
+            _ => self.format_rvalue(rvalue),
+        }
+    }
+
+    fn format_arg_list(&mut self, list: &[RValue], are_masks: bool) -> fmt::Result {
+        for (index, rvalue) in list.iter().enumerate() {
+            if index != 0 {
                 write!(self.output, ", ")?;
             }
+            self.format_argument(rvalue, index + 1 == list.len(), are_masks)?;
         }
         Ok(())
     }
+
+    // `(a, b)`, with every argument on its own line if that would be too wide
+    fn format_arguments(&mut self, arguments: &[RValue], are_masks: bool) -> fmt::Result {
+        let wrap = !arguments.is_empty()
+            && self.options.max_width.is_some()
+            && self
+                .flat_width(|f| f.format_arg_list(arguments, are_masks))
+                .is_some_and(|width| self.overflows(width + "()".len()));
+        if !wrap {
+            write!(self.output, "(")?;
+            self.format_arg_list(arguments, are_masks)?;
+            return write!(self.output, ")");
+        }
+
+        writeln!(self.output, "(")?;
+        self.indentation_level += 1;
+        for (index, rvalue) in arguments.iter().enumerate() {
+            let is_last = index + 1 == arguments.len();
+            self.indent()?;
+            self.format_argument(rvalue, is_last, are_masks)?;
+            if !is_last {
+                write!(self.output, ",")?;
+            }
+            writeln!(self.output)?;
+        }
+        self.indentation_level -= 1;
+        self.indent()?;
+        write!(self.output, ")")
+    }
     pub(crate) fn is_valid_name(name: &[u8]) -> bool {
         if !(name
             .iter()
@@ -431,11 +802,12 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
     }
 
     // TODO: PERF: Cow like from_utf8_lossy
-    pub(crate) fn escape_string(string: &[u8]) -> Cow<'_, str> {
+    // `quote` is the quote the string is printed between
+    pub(crate) fn escape_string(string: &[u8], quote: u8) -> Cow<'_, str> {
         let mut owned: Option<String> = None;
         let mut iter = string.iter().enumerate().peekable();
         while let Some((i, &c)) = iter.next() {
-            if c == b' ' || (c.is_ascii_graphic() && c != b'\\' && c != b'\'' && c != b'\"') {
+            if c == b' ' || (c.is_ascii_graphic() && c != b'\\' && c != quote) {
                 if let Some(owned) = &mut owned {
                     owned.push(c as char);
                 }
@@ -452,10 +824,12 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                     b'\n' => owned.push_str(r"\n"),
                     b'\r' => owned.push_str(r"\r"),
                     b'\t' => owned.push_str(r"\t"),
-                    b'\"' => owned.push_str(r#"\""#),
-                    b'\'' => owned.push_str(r"\'"),
                     b'\\' => owned.push_str(r"\\"),
                     12 => owned.push_str(r"\f"),
+                    _ if c == quote => {
+                        owned.push('\\');
+                        owned.push(c as char);
+                    }
                     _ => {
                         let mut buffer = itoa::Buffer::new();
                         let printed = buffer.format(c);
@@ -479,6 +853,16 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         }
     }
 
+    // braces have to be escaped between backticks too
+    pub(crate) fn escape_interpolated_string(string: &[u8]) -> Cow<'_, str> {
+        let escaped = Self::escape_string(string, b'`');
+        if escaped.contains(['{', '}']) {
+            escaped.replace('{', "\\{").replace('}', "\\}").into()
+        } else {
+            escaped
+        }
+    }
+
     pub(crate) fn format_index(&mut self, index: &Index) -> fmt::Result {
         let wrap = Self::should_wrap_left_rvalue(&index.left);
         if wrap {
@@ -511,9 +895,8 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
             write!(self.output, ")")?;
         }
 
-        write!(self.output, "(")?;
-        self.format_arg_list(&call.arguments)?;
-        write!(self.output, ")")
+        let are_masks = self.options.hex_bit_masks && Self::takes_bit_masks(&call.value);
+        self.format_arguments(&call.arguments, are_masks)
     }
 
     pub(crate) fn format_method_call(&mut self, method_call: &MethodCall) -> fmt::Result {
@@ -528,9 +911,7 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
 
         write!(self.output, ":{}", method_call.method)?;
 
-        write!(self.output, "(")?;
-        self.format_arg_list(&method_call.arguments)?;
-        write!(self.output, ")")
+        self.format_arguments(&method_call.arguments, false)
     }
 
     pub(crate) fn format_if(&mut self, r#if: &If) -> fmt::Result {
@@ -563,10 +944,6 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
     }
 
     pub(crate) fn format_assign(&mut self, assign: &Assign) -> fmt::Result {
-        if assign.prefix {
-            write!(self.output, "local ")?;
-        }
-
         if assign.left.len() == 1
             && assign.right.len() == 1
             && let RValue::Closure(closure) = &assign.right[0]
@@ -598,15 +975,24 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
                     false
                 }
             } {
-                return self.format_named_function(left, closure);
+                return self.format_named_function(assign.prefix, left, closure);
             }
         }
 
+        if assign.prefix {
+            write!(self.output, "local ")?;
+        }
+
         for (i, lvalue) in assign.left.iter().enumerate() {
             if i != 0 {
                 write!(self.output, ", ")?;
             }
             self.format_lvalue(lvalue)?;
+            if let Some(r#type) = assign.types.get(i)
+                && *r#type != Type::Any
+            {
+                write!(self.output, ": {}", r#type)?;
+            }
         }
 
         if !assign.right.is_empty() {
@@ -643,6 +1029,14 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         write!(self.output, "end")
     }
 
+    pub(crate) fn format_do(&mut self, r#do: &Do) -> fmt::Result {
+        writeln!(self.output, "do")?;
+        self.format_block(&r#do.block.lock())?;
+        writeln!(self.output)?;
+        self.indent()?;
+        write!(self.output, "end")
+    }
+
     pub(crate) fn format_repeat(&mut self, r#repeat: &Repeat) -> fmt::Result {
         writeln!(self.output, "repeat")?;
         self.format_block(&repeat.block.lock())?;
@@ -724,7 +1118,11 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
 
         match statement {
             Statement::Assign(assign) => self.format_assign(assign),
+            Statement::CompoundAssign(compound_assign) => {
+                self.format_compound_assign(compound_assign)
+            }
             Statement::If(r#if) => self.format_if(r#if),
+            Statement::Do(r#do) => self.format_do(r#do),
             Statement::While(r#while) => self.format_while(r#while),
             Statement::Repeat(repeat) => self.format_repeat(repeat),
             Statement::NumericFor(numeric_for) => self.format_numeric_for(numeric_for),
@@ -736,3 +1134,153 @@ impl<'a, W: fmt::Write> Formatter<'a, W> {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use by_address::ByAddress;
+    use parking_lot::Mutex;
+    use triomphe::Arc;
+
+    use super::*;
+    use crate::{Function, Global, Local, RcLocal};
+
+    fn number(value: f64) -> RValue {
+        Literal::Number(value).into()
+    }
+
+    fn string(value: &str) -> RValue {
+        Literal::String(value.as_bytes().to_vec()).into()
+    }
+
+    fn call(function: RValue, arguments: Vec<RValue>) -> Statement {
+        Call::new(function, arguments).into()
+    }
+
+    fn print(arguments: Vec<RValue>) -> Statement {
+        call(Global::from("print").into(), arguments)
+    }
+
+    fn formatted(statements: Vec<Statement>, options: FormatOptions) -> String {
+        let mut output = String::new();
+        Formatter::format_with_options(&Block(statements), &mut output, options).unwrap();
+        output
+    }
+
+    #[test]
+    fn wraps_past_max_width() {
+        let statement = || print(vec![string("first argument"), string("second argument")]);
+        assert_eq!(
+            formatted(vec![statement()], FormatOptions::default()),
+            "print(\"first argument\", \"second argument\")"
+        );
+        assert_eq!(
+            formatted(
+                vec![statement()],
+                FormatOptions {
+                    max_width: Some(30),
+                    ..Default::default()
+                }
+            ),
+            "print(\n\t\"first argument\",\n\t\"second argument\"\n)"
+        );
+    }
+
+    #[test]
+    fn single_quotes() {
+        assert_eq!(
+            formatted(
+                vec![print(vec![string("it's"), string("say \"hi\"")])],
+                FormatOptions {
+                    quote_style: QuoteStyle::Single,
+                    ..Default::default()
+                }
+            ),
+            "print('it\\'s', 'say \"hi\"')"
+        );
+    }
+
+    #[test]
+    fn hex_bit_masks() {
+        let band = RValue::from(Index::new(Global::from("bit32").into(), string("band")));
+        let x = RValue::from(Global::from("x"));
+        let statements = || {
+            vec![
+                call(band.clone(), vec![x.clone(), number(255.0), number(1.0)]),
+                print(vec![number(255.0)]),
+            ]
+        };
+        let options = FormatOptions {
+            hex_bit_masks: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            formatted(statements(), FormatOptions::default()),
+            "bit32.band(x, 255, 1)\nprint(255)"
+        );
+        // only the masks passed to bit32 functions, and single digits are left alone
+        assert_eq!(
+            formatted(statements(), options),
+            "bit32.band(x, 0xFF, 1)\nprint(255)"
+        );
+    }
+
+    #[test]
+    fn trailing_commas() {
+        let table = || {
+            print(vec![Table(vec![
+                (Some(string("a")), number(1.0)),
+                (Some(string("b")), number(2.0)),
+            ])
+            .into()])
+        };
+        assert_eq!(
+            formatted(vec![table()], FormatOptions::default()),
+            "print({\n\t[\"a\"] = 1,\n\t[\"b\"] = 2\n})"
+        );
+        assert_eq!(
+            formatted(
+                vec![table()],
+                FormatOptions {
+                    trailing_commas: true,
+                    ..Default::default()
+                }
+            ),
+            "print({\n\t[\"a\"] = 1,\n\t[\"b\"] = 2,\n})"
+        );
+    }
+
+    #[test]
+    fn blank_lines_between_functions() {
+        let statements = || {
+            let function = RcLocal::new(Local::new(Some("f".to_string())));
+            let mut declaration = Assign::new(
+                vec![function.into()],
+                vec![Closure {
+                    function: ByAddress(Arc::new(Mutex::new(Function::default()))),
+                    upvalues: Vec::new(),
+                }
+                .into()],
+            );
+            declaration.prefix = true;
+            vec![
+                print(vec![number(1.0)]),
+                declaration.into(),
+                print(vec![number(2.0)]),
+            ]
+        };
+        assert_eq!(
+            formatted(statements(), FormatOptions::default()),
+            "print(1)\nlocal function f() end\nprint(2)"
+        );
+        assert_eq!(
+            formatted(
+                statements(),
+                FormatOptions {
+                    blank_lines_between_functions: 1,
+                    ..Default::default()
+                }
+            ),
+            "print(1)\n\nlocal function f() end\n\nprint(2)"
+        );
+    }
+}
@@ -12,6 +12,7 @@ pub struct If {
     pub condition: RValue,
     pub then_block: Arc<Mutex<Block>>,
     pub else_block: Arc<Mutex<Block>>,
+    pub line: Option<usize>,
 }
 
 impl PartialEq for If {
@@ -27,6 +28,7 @@ impl If {
             condition,
             then_block: Arc::new(then_block.into()),
             else_block: Arc::new(else_block.into()),
+            line: None,
         }
     }
 }
@@ -62,8 +64,10 @@ impl fmt::Display for If {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         Formatter {
             indentation_level: 0,
-            indentation_mode: Default::default(),
+            options: Default::default(),
             output: f,
+            last_line: None,
+            position: None,
         }
         .format_if(self)
     }
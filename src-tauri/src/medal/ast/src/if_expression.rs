@@ -0,0 +1,126 @@
+use std::fmt;
+
+use crate::{
+    formatter::Formatter,
+    type_system::{Infer, TypeSystem},
+    LocalRw, RValue, RcLocal, Reduce, SideEffects, Traverse, Type,
+};
+
+/// `if condition then then_value else else_value`, only the chosen value is evaluated
+#[derive(Debug, Clone, PartialEq)]
+pub struct IfExpression {
+    pub condition: Box<RValue>,
+    pub then_value: Box<RValue>,
+    pub else_value: Box<RValue>,
+}
+
+impl IfExpression {
+    pub fn new(condition: RValue, then_value: RValue, else_value: RValue) -> Self {
+        Self {
+            condition: Box::new(condition),
+            then_value: Box::new(then_value),
+            else_value: Box::new(else_value),
+        }
+    }
+
+    // the value that is always chosen, if the condition is a constant
+    fn constant_branch(condition: &RValue) -> Option<bool> {
+        match condition {
+            RValue::Literal(literal) => Some(literal.is_truthy()),
+            _ => None,
+        }
+    }
+}
+
+impl SideEffects for IfExpression {
+    fn has_side_effects(&self) -> bool {
+        self.condition.has_side_effects()
+            || self.then_value.has_side_effects()
+            || self.else_value.has_side_effects()
+    }
+}
+
+impl Traverse for IfExpression {
+    fn rvalues_mut(&mut self) -> Vec<&mut RValue> {
+        vec![
+            &mut self.condition,
+            &mut self.then_value,
+            &mut self.else_value,
+        ]
+    }
+
+    fn rvalues(&self) -> Vec<&RValue> {
+        vec![&self.condition, &self.then_value, &self.else_value]
+    }
+}
+
+impl Infer for IfExpression {
+    fn infer(&self, system: &TypeSystem) -> Type {
+        self.then_value
+            .infer(system)
+            .union(self.else_value.infer(system))
+    }
+}
+
+impl Reduce for IfExpression {
+    fn reduce(self) -> RValue {
+        let condition = self.condition.reduce_condition();
+        match Self::constant_branch(&condition) {
+            Some(true) => self.then_value.reduce(),
+            Some(false) => self.else_value.reduce(),
+            None => Self::new(
+                condition,
+                self.then_value.reduce(),
+                self.else_value.reduce(),
+            )
+            .into(),
+        }
+    }
+
+    fn reduce_condition(self) -> RValue {
+        let condition = self.condition.reduce_condition();
+        match Self::constant_branch(&condition) {
+            Some(true) => self.then_value.reduce_condition(),
+            Some(false) => self.else_value.reduce_condition(),
+            None => Self::new(
+                condition,
+                self.then_value.reduce_condition(),
+                self.else_value.reduce_condition(),
+            )
+            .into(),
+        }
+    }
+}
+
+impl LocalRw for IfExpression {
+    fn values_read(&self) -> Vec<&RcLocal> {
+        self.condition
+            .values_read()
+            .into_iter()
+            .chain(self.then_value.values_read())
+            .chain(self.else_value.values_read())
+            .collect()
+    }
+
+    fn values_read_mut(&mut self) -> Vec<&mut RcLocal> {
+        self.condition
+            .values_read_mut()
+            .into_iter()
+            .chain(self.then_value.values_read_mut())
+            .chain(self.else_value.values_read_mut())
+            .collect()
+    }
+}
+
+impl fmt::Display for IfExpression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Formatter {
+            indentation_level: 0,
+            options: Default::default(),
+            output: f,
+            last_line: None,
+            position: None,
+        }
+        .format_if_expression(self)
+    }
+}
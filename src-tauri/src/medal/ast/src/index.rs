@@ -1,4 +1,9 @@
-use crate::{formatter::Formatter, has_side_effects, LocalRw, RcLocal, Traverse};
+use crate::{
+    formatter::Formatter,
+    has_side_effects,
+    type_system::{Infer, TypeSystem},
+    Literal, LocalRw, RcLocal, Traverse, Type,
+};
 
 use super::RValue;
 use std::fmt;
@@ -39,6 +44,25 @@ impl LocalRw for Index {
     }
 }
 
+impl Infer for Index {
+    fn infer(&self, system: &TypeSystem) -> Type {
+        let Type::Table { indexer, fields } = self.left.infer(system) else {
+            return Type::Any;
+        };
+        if let RValue::Literal(Literal::String(field)) = &*self.right
+            && let Some(r#type) = fields.get(String::from_utf8_lossy(field).as_ref())
+        {
+            return r#type.clone();
+        }
+        match indexer {
+            Some(box (key_type, element_type)) if self.right.infer(system) == key_type => {
+                element_type
+            }
+            _ => Type::Any,
+        }
+    }
+}
+
 impl Traverse for Index {
     fn rvalues_mut(&mut self) -> Vec<&mut RValue> {
         vec![&mut self.left, &mut self.right]
@@ -53,8 +77,10 @@ impl fmt::Display for Index {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         Formatter {
             indentation_level: 0,
-            indentation_mode: Default::default(),
+            options: Default::default(),
             output: f,
+            last_line: None,
+            position: None,
         }
         .format_index(self)
     }
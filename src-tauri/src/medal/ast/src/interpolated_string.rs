@@ -0,0 +1,79 @@
+use std::fmt;
+
+use crate::{formatter::Formatter, LocalRw, RValue, RcLocal, SideEffects, Traverse};
+
+/// `` `a{b}c` ``, `strings` has one more element than `values`
+#[derive(Debug, Clone, PartialEq)]
+pub struct InterpolatedString {
+    pub strings: Vec<Vec<u8>>,
+    pub values: Vec<RValue>,
+}
+
+impl InterpolatedString {
+    pub fn new(strings: Vec<Vec<u8>>, values: Vec<RValue>) -> Self {
+        assert_eq!(strings.len(), values.len() + 1);
+        Self { strings, values }
+    }
+
+    /// Splits a format string the way the compiler generates it for an
+    /// interpolated string, i.e. only `%*` and `%%` specifiers
+    pub fn split_format(format: &[u8]) -> Option<Vec<Vec<u8>>> {
+        let mut strings = vec![Vec::new()];
+        let mut iter = format.iter();
+        while let Some(&c) = iter.next() {
+            if c == b'%' {
+                match iter.next()? {
+                    b'%' => strings.last_mut().unwrap().push(b'%'),
+                    b'*' => strings.push(Vec::new()),
+                    _ => return None,
+                }
+            } else {
+                strings.last_mut().unwrap().push(c);
+            }
+        }
+        Some(strings)
+    }
+}
+
+impl SideEffects for InterpolatedString {
+    fn has_side_effects(&self) -> bool {
+        // __tostring can have side effects
+        !self.values.is_empty()
+    }
+}
+
+impl Traverse for InterpolatedString {
+    fn rvalues_mut(&mut self) -> Vec<&mut RValue> {
+        self.values.iter_mut().collect()
+    }
+
+    fn rvalues(&self) -> Vec<&RValue> {
+        self.values.iter().collect()
+    }
+}
+
+impl LocalRw for InterpolatedString {
+    fn values_read(&self) -> Vec<&RcLocal> {
+        self.values.iter().flat_map(|r| r.values_read()).collect()
+    }
+
+    fn values_read_mut(&mut self) -> Vec<&mut RcLocal> {
+        self.values
+            .iter_mut()
+            .flat_map(|r| r.values_read_mut())
+            .collect()
+    }
+}
+
+impl fmt::Display for InterpolatedString {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Formatter {
+            indentation_level: 0,
+            options: Default::default(),
+            output: f,
+            last_line: None,
+            position: None,
+        }
+        .format_interpolated_string(self)
+    }
+}
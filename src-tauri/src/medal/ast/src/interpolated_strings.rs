@@ -0,0 +1,73 @@
+use itertools::Either;
+
+use crate::{Block, InterpolatedString, Literal, MethodCall, RValue, Select, Statement, Traverse};
+
+fn reconstruct(method_call: &mut MethodCall) -> Option<InterpolatedString> {
+    let RValue::Literal(Literal::String(format)) = &*method_call.value else {
+        return None;
+    };
+    // the last argument can't be more or less than one value, that would change the number of
+    // arguments. truncated calls (`Select`) are fine, that's what a call in a hole compiles to
+    if method_call.method != "format"
+        || matches!(
+            method_call.arguments.last(),
+            None | Some(RValue::Call(_) | RValue::MethodCall(_) | RValue::VarArg(_))
+        )
+    {
+        return None;
+    }
+    let strings = InterpolatedString::split_format(format)?;
+    if strings.len() != method_call.arguments.len() + 1 {
+        return None;
+    }
+    Some(InterpolatedString::new(
+        strings,
+        std::mem::take(&mut method_call.arguments),
+    ))
+}
+
+/// Turns `("a %* c"):format(b)`, which is what the compiler generates for
+/// `` `a {b} c` ``, back into an interpolated string
+pub fn reconstruct_interpolated_strings(block: &mut Block) {
+    for statement in &mut block.0 {
+        statement.post_traverse_values(&mut |value| -> Option<()> {
+            if let Either::Right(rvalue) = value {
+                match rvalue {
+                    RValue::MethodCall(method_call)
+                    | RValue::Select(Select::MethodCall(method_call)) => {
+                        if let Some(interpolated_string) = reconstruct(method_call) {
+                            *rvalue = interpolated_string.into();
+                        }
+                    }
+                    RValue::Closure(closure) => {
+                        reconstruct_interpolated_strings(&mut closure.function.lock().body)
+                    }
+                    _ => {}
+                }
+            };
+            None
+        });
+        match statement {
+            Statement::If(r#if) => {
+                reconstruct_interpolated_strings(&mut r#if.then_block.lock());
+                reconstruct_interpolated_strings(&mut r#if.else_block.lock());
+            }
+            Statement::While(r#while) => {
+                reconstruct_interpolated_strings(&mut r#while.block.lock());
+            }
+            Statement::Do(r#do) => {
+                reconstruct_interpolated_strings(&mut r#do.block.lock());
+            }
+            Statement::Repeat(repeat) => {
+                reconstruct_interpolated_strings(&mut repeat.block.lock());
+            }
+            Statement::NumericFor(numeric_for) => {
+                reconstruct_interpolated_strings(&mut numeric_for.block.lock());
+            }
+            Statement::GenericFor(generic_for) => {
+                reconstruct_interpolated_strings(&mut generic_for.block.lock());
+            }
+            _ => {}
+        }
+    }
+}
@@ -17,23 +17,32 @@ mod r#break;
 mod call;
 mod close;
 mod closure;
+mod compound_assign;
+pub mod compound_assignments;
 mod r#continue;
+mod r#do;
+pub mod export;
 mod r#for;
 pub mod formatter;
 mod global;
 mod goto;
 mod r#if;
+mod if_expression;
 mod index;
+mod interpolated_string;
+pub mod interpolated_strings;
 mod literal;
 mod local;
-//mod name_gen;
 pub mod local_declarations;
+pub mod name_gen;
 pub mod name_locals;
 mod repeat;
 pub mod replace_locals;
 mod r#return;
+pub mod roblox;
 mod set_list;
 mod side_effects;
+pub mod simplify;
 mod table;
 mod traverse;
 pub mod type_system;
@@ -46,13 +55,17 @@ pub use binary::*;
 pub use call::*;
 pub use close::*;
 pub use closure::*;
+pub use compound_assign::*;
 pub use global::*;
 pub use goto::*;
+pub use if_expression::*;
 pub use index::*;
+pub use interpolated_string::*;
 pub use literal::*;
 pub use local::*;
 pub use r#break::*;
 pub use r#continue::*;
+pub use r#do::*;
 pub use r#for::*;
 pub use r#if::*;
 pub use r#return::*;
@@ -104,20 +117,26 @@ pub enum RValue {
     Binary(Binary),
     Closure(Closure),
     Select(Select),
+    IfExpression(IfExpression),
+    InterpolatedString(InterpolatedString),
 }
 
 impl type_system::Infer for RValue {
-    fn infer<'a: 'b, 'b>(&'a mut self, system: &mut TypeSystem<'b>) -> Type {
+    fn infer(&self, system: &TypeSystem) -> Type {
         match self {
             RValue::Local(local) => local.infer(system),
             RValue::Global(_) => Type::Any,
-            RValue::Call(_) => Type::Any,
-            //RValue::Table(table) => table.infer(system),
+            RValue::Call(call) => call.infer(system),
+            RValue::Table(table) => table.infer(system),
             RValue::Literal(literal) => literal.infer(system),
-            RValue::Index(_) => Type::Any,
-            RValue::Unary(_) => Type::Any,
-            RValue::Binary(_) => Type::Any,
+            RValue::Index(index) => index.infer(system),
+            RValue::Unary(unary) => unary.infer(system),
+            RValue::Binary(binary) => binary.infer(system),
             RValue::Closure(closure) => closure.infer(system),
+            RValue::IfExpression(if_expression) => if_expression.infer(system),
+            RValue::InterpolatedString(_) => Type::String,
+            // the first value, a select could evaluate to more than one
+            RValue::Select(Select::Call(call)) => call.infer(system),
             _ => Type::VarArg,
         }
     }
@@ -131,6 +150,7 @@ impl<'a: 'b, 'b> Reduce for RValue {
             Self::Literal(literal) => literal.reduce(),
             Self::Table(table) => table.reduce(),
             Self::Closure(closure) => closure.reduce(),
+            Self::IfExpression(if_expression) => if_expression.reduce(),
             other => other,
         }
     }
@@ -142,6 +162,7 @@ impl<'a: 'b, 'b> Reduce for RValue {
             Self::Literal(literal) => literal.reduce_condition(),
             Self::Table(table) => table.reduce_condition(),
             Self::Closure(closure) => closure.reduce_condition(),
+            Self::IfExpression(if_expression) => if_expression.reduce_condition(),
             other => other,
         }
     }
@@ -155,6 +176,8 @@ impl RValue {
             RValue::Literal(Literal::Number(n)) if n.is_finite() && n.is_sign_negative() => {
                 return 7;
             }
+            // extends as far right as possible, so it's always grouped
+            Self::IfExpression(_) => 0,
             _ => 9,
         }
     }
@@ -184,6 +207,10 @@ impl fmt::Display for RValue {
             RValue::Binary(binary) => write!(f, "{}", binary),
             RValue::Closure(closure) => write!(f, "{}", closure),
             RValue::Select(select) => write!(f, "{}", select),
+            RValue::IfExpression(if_expression) => write!(f, "{}", if_expression),
+            RValue::InterpolatedString(interpolated_string) => {
+                write!(f, "{}", interpolated_string)
+            }
         }
     }
 }
@@ -264,7 +291,9 @@ pub enum Statement {
     Call(Call),
     MethodCall(MethodCall),
     Assign(Assign),
+    CompoundAssign(CompoundAssign),
     If(If),
+    Do(Do),
     Goto(Goto),
     Label(Label),
     While(While),
@@ -283,6 +312,33 @@ pub enum Statement {
     Comment(Comment),
 }
 
+impl Statement {
+    /// The source line this statement was lifted from, if debug info was available
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            Statement::Call(call) => call.line,
+            Statement::MethodCall(method_call) => method_call.line,
+            Statement::Assign(assign) => assign.line,
+            Statement::CompoundAssign(compound_assign) => compound_assign.line,
+            Statement::If(r#if) => r#if.line,
+            Statement::Return(r#return) => r#return.line,
+            _ => None,
+        }
+    }
+
+    pub fn set_line(&mut self, line: Option<usize>) {
+        match self {
+            Statement::Call(call) => call.line = line,
+            Statement::MethodCall(method_call) => method_call.line = line,
+            Statement::Assign(assign) => assign.line = line,
+            Statement::CompoundAssign(compound_assign) => compound_assign.line = line,
+            Statement::If(r#if) => r#if.line = line,
+            Statement::Return(r#return) => r#return.line = line,
+            _ => {}
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct Empty {}
 
@@ -300,7 +356,12 @@ impl fmt::Display for Empty {
 
 impl fmt::Display for Comment {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "-- {}", self.text)
+        // hot comments like `--!native` must directly follow the dashes
+        if self.text.starts_with('!') {
+            write!(f, "--{}", self.text)
+        } else {
+            write!(f, "-- {}", self.text)
+        }
     }
 }
 
@@ -311,8 +372,10 @@ impl fmt::Display for Statement {
             Statement::Call(call) => write!(f, "{}", call),
             Statement::MethodCall(method_call) => write!(f, "{}", method_call),
             Statement::Assign(assign) => write!(f, "{}", assign),
+            Statement::CompoundAssign(compound_assign) => write!(f, "{}", compound_assign),
             // TODO: STYLE: replace all `if_` with `r#if`, etc
             Statement::If(if_) => write!(f, "{}", if_),
+            Statement::Do(r#do) => write!(f, "{}", r#do),
             Statement::Goto(goto) => write!(f, "{}", goto),
             Statement::Label(label) => write!(f, "{}", label),
             Statement::While(while_) => write!(f, "{}", while_),
@@ -16,6 +16,13 @@ pub enum Literal {
     Vector(f32, f32, f32),
 }
 
+impl Literal {
+    /// Whether the value counts as true in a condition
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Literal::Nil | Literal::Boolean(false))
+    }
+}
+
 impl Reduce for Literal {
     fn reduce(self) -> crate::RValue {
         self.into()
@@ -34,7 +41,7 @@ impl Reduce for Literal {
 }
 
 impl Infer for Literal {
-    fn infer<'a: 'b, 'b>(&'a mut self, _: &mut TypeSystem<'b>) -> Type {
+    fn infer(&self, _: &TypeSystem) -> Type {
         match self {
             Literal::Nil => Type::Nil,
             Literal::Boolean(_) => Type::Boolean,
@@ -75,7 +82,7 @@ impl fmt::Display for Literal {
                 write!(
                     f,
                     "\"{}\"",
-                    Formatter::<fmt::Formatter>::escape_string(value)
+                    Formatter::<fmt::Formatter>::escape_string(value, b'"')
                 )
             }
             Literal::Vector(x, y, z) => write!(f, "Vector3.new({}, {}, {})", x, y, z),
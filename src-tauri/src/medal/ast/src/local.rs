@@ -32,7 +32,7 @@ impl fmt::Display for Local {
 pub struct RcLocal(pub ByAddress<Arc<Mutex<Local>>>);
 
 impl Infer for RcLocal {
-    fn infer<'a: 'b, 'b>(&'a mut self, system: &mut TypeSystem<'b>) -> Type {
+    fn infer(&self, system: &TypeSystem) -> Type {
         system.type_of(self).clone()
     }
 }
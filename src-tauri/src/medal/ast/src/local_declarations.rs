@@ -54,6 +54,10 @@ impl LocalDeclarer {
                     let child = self.visit(r#while.block.clone(), stat_index);
                     self.graph.add_edge(node, child, ());
                 }
+                Statement::Do(r#do) => {
+                    let child = self.visit(r#do.block.clone(), stat_index);
+                    self.graph.add_edge(node, child, ());
+                }
                 Statement::Repeat(repeat) => {
                     let child = self.visit(r#repeat.block.clone(), stat_index);
                     self.graph.add_edge(node, child, ());
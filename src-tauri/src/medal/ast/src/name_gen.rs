@@ -1,7 +1,56 @@
-use super::{Literal, RValue};
+use super::{
+    BinaryOperation, Block, Call, LValue, Literal, MethodCall, RValue, RcLocal, Select, Statement,
+    Traverse, UnaryOperation,
+};
 
 pub trait NameGenerator {
+    /// A name for a local from the value it's declared with
     fn generate_name(&self, rvalue: &RValue, identifier: usize) -> Option<String>;
+
+    /// Names for the variables of a generic for loop over `values`
+    fn generate_generic_for_names(&self, _values: &[RValue], count: usize) -> Vec<Option<String>> {
+        vec![None; count]
+    }
+
+    fn generate_numeric_for_name(&self) -> Option<String> {
+        None
+    }
+
+    /// A name for a function parameter from how it's used in `body`
+    fn generate_parameter_name(&self, _parameter: &RcLocal, _body: &Block) -> Option<String> {
+        None
+    }
+}
+
+/// Which [`NameGenerator`] `name_locals` uses for locals without debug names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NamingStrategy {
+    /// `v1`, `v2`, ... and `p1`, `p2`, ... for parameters
+    #[default]
+    Numbered,
+    /// `v_1_hint` where the hint is the global or field a local is declared with
+    Hinted,
+    /// Names derived from common Roblox APIs and conventions, e.g. `Players`
+    /// for `game:GetService("Players")` and `i, v` for `ipairs` loops
+    Roblox,
+}
+
+impl NamingStrategy {
+    pub fn name_generator(self) -> &'static dyn NameGenerator {
+        match self {
+            NamingStrategy::Numbered => &NumberedNameGenerator {},
+            NamingStrategy::Hinted => &DefaultNameGenerator {},
+            NamingStrategy::Roblox => &RobloxNameGenerator {},
+        }
+    }
+}
+
+pub struct NumberedNameGenerator {}
+
+impl NameGenerator for NumberedNameGenerator {
+    fn generate_name(&self, _rvalue: &RValue, _identifier: usize) -> Option<String> {
+        None
+    }
 }
 
 pub struct DefaultNameGenerator {}
@@ -9,9 +58,11 @@ pub struct DefaultNameGenerator {}
 impl NameGenerator for DefaultNameGenerator {
     fn generate_name(&self, rvalue: &RValue, identifier: usize) -> Option<String> {
         let hint = match rvalue {
-            RValue::Global(global) => Some(global.to_string()),
+            RValue::Global(global) => Some(String::from_utf8_lossy(&global.0).into_owned()),
             RValue::Index(index) => match &*index.right {
-                RValue::Literal(Literal::String(string)) => Some(string.to_string()),
+                RValue::Literal(Literal::String(string)) => {
+                    Some(String::from_utf8_lossy(string).into_owned())
+                }
                 _ => None,
             },
             _ => None,
@@ -19,3 +70,468 @@ impl NameGenerator for DefaultNameGenerator {
         hint.map(|hint| format!("v_{}_{}", identifier, hint))
     }
 }
+
+// instance methods that return the child/ancestor with the name or class given
+const FIND_METHODS: &[&str] = &[
+    "WaitForChild",
+    "FindFirstChild",
+    "FindFirstChildOfClass",
+    "FindFirstChildWhichIsA",
+    "FindFirstAncestor",
+    "FindFirstAncestorOfClass",
+    "FindFirstAncestorWhichIsA",
+];
+
+const INSTANCE_METHODS: &[&str] = &[
+    "WaitForChild",
+    "FindFirstChild",
+    "FindFirstChildOfClass",
+    "FindFirstChildWhichIsA",
+    "FindFirstAncestor",
+    "GetChildren",
+    "GetDescendants",
+    "IsA",
+    "IsDescendantOf",
+    "Destroy",
+    "Clone",
+    "GetAttribute",
+    "SetAttribute",
+    "GetPropertyChangedSignal",
+];
+
+const STRING_METHODS: &[&str] = &[
+    "byte", "find", "format", "gmatch", "gsub", "len", "lower", "match", "rep", "reverse", "split",
+    "sub", "upper",
+];
+
+const PLAYER_FIELDS: &[&str] = &[
+    "Character",
+    "UserId",
+    "DisplayName",
+    "Backpack",
+    "PlayerGui",
+    "Team",
+];
+
+const INSTANCE_FIELDS: &[&str] = &["Parent", "ClassName", "Name"];
+
+// fields that hold an instance that's usually named after the field
+const NAMED_FIELDS: &[&str] = &[
+    "LocalPlayer",
+    "Character",
+    "PlayerGui",
+    "Backpack",
+    "CurrentCamera",
+    "Parent",
+];
+
+// `UIListLayout` -> `uiListLayout`, `Part` -> `part`
+fn lower_camel_case(name: &str) -> String {
+    let upper = name.chars().take_while(char::is_ascii_uppercase).count();
+    let lower = match upper {
+        0 => 0,
+        _ if upper == name.chars().count() => upper,
+        1 => 1,
+        // the last capital starts the next word
+        _ => upper - 1,
+    };
+    name.chars()
+        .enumerate()
+        .map(|(i, c)| if i < lower { c.to_ascii_lowercase() } else { c })
+        .collect()
+}
+
+fn string_argument(arguments: &[RValue]) -> Option<&str> {
+    match arguments.first()? {
+        RValue::Literal(Literal::String(string)) => std::str::from_utf8(string).ok(),
+        _ => None,
+    }
+}
+
+fn global_name(rvalue: &RValue) -> Option<&[u8]> {
+    match rvalue {
+        RValue::Global(global) => Some(&global.0),
+        _ => None,
+    }
+}
+
+fn field_name(rvalue: &RValue) -> Option<&str> {
+    match rvalue {
+        RValue::Literal(Literal::String(string)) => std::str::from_utf8(string).ok(),
+        _ => None,
+    }
+}
+
+// a value or call visited by `visit_values`, calls are visited the same way whether
+// they're statements or values
+enum Node<'a> {
+    LValue(&'a LValue),
+    RValue(&'a RValue),
+    Call(&'a Call),
+    MethodCall(&'a MethodCall),
+}
+
+// calls `callback` with every value in `block`, including nested blocks and closures
+fn visit_values(block: &Block, callback: &mut impl FnMut(Node)) {
+    fn visit_rvalue(rvalue: &RValue, callback: &mut impl FnMut(Node)) {
+        match rvalue {
+            RValue::Call(call) | RValue::Select(Select::Call(call)) => callback(Node::Call(call)),
+            RValue::MethodCall(method_call) | RValue::Select(Select::MethodCall(method_call)) => {
+                callback(Node::MethodCall(method_call))
+            }
+            RValue::Closure(closure) => visit_values(&closure.function.lock().body, callback),
+            _ => callback(Node::RValue(rvalue)),
+        }
+        for rvalue in rvalue.rvalues() {
+            visit_rvalue(rvalue, callback);
+        }
+    }
+
+    fn visit_lvalue(lvalue: &LValue, callback: &mut impl FnMut(Node)) {
+        callback(Node::LValue(lvalue));
+        if let LValue::Index(index) = lvalue {
+            for rvalue in index.rvalues() {
+                visit_rvalue(rvalue, callback);
+            }
+        }
+    }
+
+    for statement in block.iter() {
+        match statement {
+            Statement::Assign(assign) => {
+                for lvalue in &assign.left {
+                    visit_lvalue(lvalue, callback);
+                }
+            }
+            Statement::CompoundAssign(compound_assign) => {
+                visit_lvalue(&compound_assign.left, callback);
+            }
+            Statement::Call(call) => callback(Node::Call(call)),
+            Statement::MethodCall(method_call) => callback(Node::MethodCall(method_call)),
+            _ => {}
+        }
+        for rvalue in statement.rvalues() {
+            visit_rvalue(rvalue, callback);
+        }
+        match statement {
+            Statement::If(r#if) => {
+                visit_values(&r#if.then_block.lock(), callback);
+                visit_values(&r#if.else_block.lock(), callback);
+            }
+            Statement::While(r#while) => {
+                visit_values(&r#while.block.lock(), callback);
+            }
+            Statement::Do(r#do) => {
+                visit_values(&r#do.block.lock(), callback);
+            }
+            Statement::Repeat(repeat) => {
+                visit_values(&repeat.block.lock(), callback);
+            }
+            Statement::NumericFor(numeric_for) => {
+                visit_values(&numeric_for.block.lock(), callback);
+            }
+            Statement::GenericFor(generic_for) => {
+                visit_values(&generic_for.block.lock(), callback);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The names of all globals read or written in `block`
+pub(crate) fn global_names(block: &Block) -> Vec<String> {
+    let mut names = Vec::new();
+    visit_values(block, &mut |node| {
+        if let Node::LValue(LValue::Global(global)) | Node::RValue(RValue::Global(global)) = node {
+            names.push(String::from_utf8_lossy(&global.0).into_owned());
+        }
+    });
+    names
+}
+
+pub struct RobloxNameGenerator {}
+
+impl RobloxNameGenerator {
+    // the name of the element type of the array `rvalue` evaluates to, i.e. `child` for
+    // `x:GetChildren()`
+    fn element_name(rvalue: &RValue) -> Option<&'static str> {
+        let (RValue::MethodCall(method_call) | RValue::Select(Select::MethodCall(method_call))) =
+            rvalue
+        else {
+            return None;
+        };
+        match method_call.method.as_str() {
+            "GetChildren" => Some("child"),
+            "GetDescendants" => Some("descendant"),
+            "GetPlayers" => Some("player"),
+            _ => None,
+        }
+    }
+
+    // usages earlier in the list are better hints
+    const PARAMETER_NAMES: &'static [&'static str] =
+        &["player", "instance", "callback", "str", "list", "tbl"];
+
+    fn parameter_usage(parameter: &RcLocal, node: Node) -> Option<&'static str> {
+        let is_parameter = |rvalue: &RValue| rvalue.as_local() == Some(parameter);
+        match node {
+            Node::Call(call) => {
+                if is_parameter(&call.value) {
+                    Some("callback")
+                } else if call.arguments.first().is_some_and(is_parameter) {
+                    match global_name(&call.value)? {
+                        b"ipairs" => Some("list"),
+                        b"pairs" => Some("tbl"),
+                        _ => None,
+                    }
+                } else {
+                    None
+                }
+            }
+            Node::MethodCall(method_call) if is_parameter(&method_call.value) => {
+                let method = method_call.method.as_str();
+                if INSTANCE_METHODS.contains(&method) {
+                    Some("instance")
+                } else if STRING_METHODS.contains(&method) {
+                    Some("str")
+                } else {
+                    None
+                }
+            }
+            Node::RValue(RValue::Index(index)) if is_parameter(&index.left) => {
+                let field = field_name(&index.right)?;
+                if PLAYER_FIELDS.contains(&field) {
+                    Some("player")
+                } else if INSTANCE_FIELDS.contains(&field) {
+                    Some("instance")
+                } else {
+                    None
+                }
+            }
+            Node::RValue(RValue::Unary(unary))
+                if unary.operation == UnaryOperation::Length && is_parameter(&unary.value) =>
+            {
+                Some("list")
+            }
+            _ => None,
+        }
+    }
+}
+
+impl NameGenerator for RobloxNameGenerator {
+    fn generate_name(&self, rvalue: &RValue, _identifier: usize) -> Option<String> {
+        match rvalue {
+            RValue::MethodCall(method_call) | RValue::Select(Select::MethodCall(method_call)) => {
+                let argument = string_argument(&method_call.arguments)?;
+                if method_call.method == "GetService" {
+                    Some(argument.to_string())
+                } else if FIND_METHODS.contains(&method_call.method.as_str()) {
+                    Some(lower_camel_case(argument))
+                } else {
+                    None
+                }
+            }
+            RValue::Call(call) | RValue::Select(Select::Call(call)) => {
+                let RValue::Index(index) = &*call.value else {
+                    return None;
+                };
+                if global_name(&index.left) == Some(b"Instance")
+                    && field_name(&index.right) == Some("new")
+                {
+                    string_argument(&call.arguments).map(lower_camel_case)
+                } else {
+                    None
+                }
+            }
+            RValue::Index(index) => field_name(&index.right)
+                .filter(|field| NAMED_FIELDS.contains(field))
+                .map(lower_camel_case),
+            // `player.Character or player.CharacterAdded:Wait()`
+            RValue::Binary(binary) if binary.operation == BinaryOperation::Or => {
+                self.generate_name(&binary.left, _identifier)
+            }
+            _ => None,
+        }
+    }
+
+    fn generate_generic_for_names(&self, values: &[RValue], count: usize) -> Vec<Option<String>> {
+        let (key, value) = match values {
+            [RValue::Call(call) | RValue::Select(Select::Call(call)), ..]
+                if global_name(&call.value) == Some(b"ipairs") =>
+            {
+                let element = call.arguments.first().and_then(Self::element_name);
+                ("i", element.unwrap_or("v"))
+            }
+            [value] => match Self::element_name(value) {
+                Some(element) => ("i", element),
+                None => ("key", "value"),
+            },
+            _ => ("key", "value"),
+        };
+        [key, value]
+            .into_iter()
+            .map(|name| Some(name.to_string()))
+            .chain(std::iter::repeat(None))
+            .take(count)
+            .collect()
+    }
+
+    fn generate_numeric_for_name(&self) -> Option<String> {
+        Some("i".to_string())
+    }
+
+    fn generate_parameter_name(&self, parameter: &RcLocal, body: &Block) -> Option<String> {
+        let mut best: Option<&'static str> = None;
+        visit_values(body, &mut |node| {
+            if let Some(name) = Self::parameter_usage(parameter, node)
+                && best.is_none_or(|best| {
+                    Self::PARAMETER_NAMES.iter().position(|&n| n == name)
+                        < Self::PARAMETER_NAMES.iter().position(|&n| n == best)
+                })
+            {
+                best = Some(name);
+            }
+        });
+        best.map(str::to_string)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Assign, Global, Index, Unary};
+
+    fn string(string: &str) -> RValue {
+        Literal::String(string.as_bytes().to_vec()).into()
+    }
+
+    fn global(name: &str) -> RValue {
+        Global::new(name.as_bytes().to_vec()).into()
+    }
+
+    fn call(function: &str, arguments: Vec<RValue>) -> RValue {
+        Call::new(global(function), arguments).into()
+    }
+
+    fn method_call(value: RValue, method: &str, arguments: Vec<RValue>) -> MethodCall {
+        MethodCall::new(value, method.to_string(), arguments)
+    }
+
+    fn name(rvalue: impl Into<RValue>) -> Option<String> {
+        RobloxNameGenerator {}.generate_name(&rvalue.into(), 1)
+    }
+
+    fn for_names(values: Vec<RValue>) -> Vec<Option<String>> {
+        RobloxNameGenerator {}.generate_generic_for_names(&values, 3)
+    }
+
+    fn parameter_name(parameter: &RcLocal, body: Vec<Statement>) -> Option<String> {
+        RobloxNameGenerator {}.generate_parameter_name(parameter, &Block(body))
+    }
+
+    fn read(rvalue: impl Into<RValue>) -> Statement {
+        Assign::new(vec![RcLocal::default().into()], vec![rvalue.into()]).into()
+    }
+
+    #[test]
+    fn lower_camel_case_names() {
+        assert_eq!(lower_camel_case("Part"), "part");
+        assert_eq!(lower_camel_case("UIListLayout"), "uiListLayout");
+        assert_eq!(lower_camel_case("GUI"), "gui");
+        assert_eq!(lower_camel_case("leaderstats"), "leaderstats");
+        assert_eq!(lower_camel_case("ÄPart"), "ÄPart");
+        assert_eq!(lower_camel_case("ABÄ"), "aBÄ");
+    }
+
+    #[test]
+    fn service_names() {
+        let service = method_call(global("game"), "GetService", vec![string("Players")]);
+        assert_eq!(name(service).as_deref(), Some("Players"));
+        let service = method_call(global("game"), "GetService", vec![global("name")]);
+        assert_eq!(name(service), None);
+    }
+
+    #[test]
+    fn instance_new_names() {
+        let new = Index::new(global("Instance"), string("new"));
+        let instance = Call::new(new.into(), vec![string("UIListLayout")]);
+        assert_eq!(name(instance).as_deref(), Some("uiListLayout"));
+    }
+
+    #[test]
+    fn find_method_names() {
+        let child = method_call(
+            global("script"),
+            "WaitForChild",
+            vec![string("RemoteEvent")],
+        );
+        assert_eq!(name(child).as_deref(), Some("remoteEvent"));
+        let child = method_call(global("script"), "FindFirstChild", vec![string("HUD")]);
+        assert_eq!(name(child).as_deref(), Some("hud"));
+        let children = method_call(global("script"), "GetChildren", Vec::new());
+        assert_eq!(name(children), None);
+    }
+
+    #[test]
+    fn generic_for_names() {
+        let children = method_call(global("script"), "GetChildren", Vec::new());
+        assert_eq!(
+            for_names(vec![call("ipairs", vec![global("list")])]),
+            [Some("i".to_string()), Some("v".to_string()), None]
+        );
+        assert_eq!(
+            for_names(vec![call("ipairs", vec![children.clone().into()])]),
+            [Some("i".to_string()), Some("child".to_string()), None]
+        );
+        assert_eq!(
+            for_names(vec![children.into()]),
+            [Some("i".to_string()), Some("child".to_string()), None]
+        );
+        assert_eq!(
+            for_names(vec![call("pairs", vec![global("map")])]),
+            [Some("key".to_string()), Some("value".to_string()), None]
+        );
+    }
+
+    #[test]
+    fn parameter_names() {
+        let parameter = RcLocal::default();
+        let value = || RValue::Local(parameter.clone());
+        let find = method_call(value(), "FindFirstChild", vec![string("Head")]);
+        let character = Index::new(value(), string("Character"));
+        assert_eq!(
+            parameter_name(&parameter, vec![find.clone().into()]).as_deref(),
+            Some("instance")
+        );
+        // player is a better hint than instance
+        assert_eq!(
+            parameter_name(&parameter, vec![find.into(), read(character)]).as_deref(),
+            Some("player")
+        );
+        assert_eq!(
+            parameter_name(&parameter, vec![Call::new(value(), Vec::new()).into()]).as_deref(),
+            Some("callback")
+        );
+        assert_eq!(
+            parameter_name(
+                &parameter,
+                vec![read(method_call(value(), "upper", Vec::new()))]
+            )
+            .as_deref(),
+            Some("str")
+        );
+        assert_eq!(
+            parameter_name(
+                &parameter,
+                vec![read(Unary::new(value(), UnaryOperation::Length))]
+            )
+            .as_deref(),
+            Some("list")
+        );
+        assert_eq!(
+            parameter_name(&parameter, vec![read(call("pairs", vec![value()]))]).as_deref(),
+            Some("tbl")
+        );
+        assert_eq!(parameter_name(&parameter, vec![read(value())]), None);
+    }
+}
@@ -1,72 +1,271 @@
-use rustc_hash::FxHashSet;
+use std::fmt;
+
+use rustc_hash::{FxHashMap, FxHashSet};
 use triomphe::Arc;
 
-use crate::{Block, RValue, RcLocal, Statement, Traverse, Upvalue};
+use crate::{
+    formatter::Formatter,
+    name_gen::{global_names, NameGenerator},
+    Block, LocalRw, RValue, RcLocal, Statement, Traverse, Upvalue,
+};
+
+// the statements a local is in scope for, and the condition of the `repeat`
+// whose body it's declared in, which can still see it
+#[derive(Clone, Copy)]
+struct Scope<'a> {
+    statements: &'a [Statement],
+    until: Option<&'a RValue>,
+}
+
+impl Scope<'_> {
+    fn uses(&self, local: &RcLocal) -> bool {
+        uses(self.statements, local) || self.until.is_some_and(|c| c.values().contains(&local))
+    }
+}
 
-struct Namer {
+// whether `local` is used anywhere in `statements`, including in nested blocks and closures
+fn uses(statements: &[Statement], local: &RcLocal) -> bool {
+    statements.iter().any(|statement| {
+        statement.values().contains(&local)
+            || match statement {
+                Statement::If(r#if) => {
+                    uses(&r#if.then_block.lock(), local) || uses(&r#if.else_block.lock(), local)
+                }
+                Statement::While(r#while) => uses(&r#while.block.lock(), local),
+                Statement::Do(r#do) => uses(&r#do.block.lock(), local),
+                Statement::Repeat(repeat) => uses(&repeat.block.lock(), local),
+                Statement::NumericFor(numeric_for) => uses(&numeric_for.block.lock(), local),
+                Statement::GenericFor(generic_for) => uses(&generic_for.block.lock(), local),
+                _ => false,
+            }
+    })
+}
+
+struct Namer<'a> {
     rename: bool,
     counter: usize,
     upvalues: FxHashSet<RcLocal>,
+    names: FxHashMap<String, RcLocal>,
+    // the locals visible at the current point, innermost scope last
+    scopes: Vec<FxHashMap<String, RcLocal>>,
+    // generated names can't shadow these
+    globals: FxHashSet<String>,
+    name_generator: &'a dyn NameGenerator,
 }
 
-impl Namer {
-    fn name_local(&mut self, prefix: &str, local: &RcLocal) {
+impl Namer<'_> {
+    fn unique_name(&mut self, name: String, local: &RcLocal) -> String {
+        let mut unique_name = name.clone();
+        let mut suffix = 1;
+        while let Some(other) = self.names.get(&unique_name)
+            && other != local
+        {
+            suffix += 1;
+            unique_name = format!("{}_{}", name, suffix);
+        }
+        self.names.insert(unique_name.clone(), local.clone());
+        unique_name
+    }
+
+    fn visible(&self, name: &str) -> Option<&RcLocal> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    // locals are declared in different places than in the source, so names from debug info are
+    // only changed if the local would shadow another one that's still used in its scope
+    fn unshadowed_name(&mut self, name: String, local: &RcLocal, scope: Scope) -> String {
+        if name == "_" {
+            return name;
+        }
+        let mut unshadowed_name = name.clone();
+        let mut suffix = 1;
+        while let Some(other) = self.visible(&unshadowed_name)
+            && other != local
+            && scope.uses(other)
+        {
+            suffix += 1;
+            unshadowed_name = format!("{}_{}", name, suffix);
+            while self.visible(&unshadowed_name).is_some()
+                || self.globals.contains(&unshadowed_name)
+            {
+                suffix += 1;
+                unshadowed_name = format!("{}_{}", name, suffix);
+            }
+        }
+        self.names.insert(unshadowed_name.clone(), local.clone());
+        unshadowed_name
+    }
+
+    fn name_local(&mut self, prefix: &str, local: &RcLocal, hint: Option<String>, scope: Scope) {
         let mut lock = local.0 .0.lock();
-        if self.rename || lock.0.is_none() {
+        if let Some(name) = &lock.0
+            && !Formatter::<fmt::Formatter>::is_valid_name(name.as_bytes())
+        {
+            lock.0 = None;
+        }
+        if !self.rename
+            && let Some(name) = lock.0.clone()
+        {
+            lock.0 = Some(self.unshadowed_name(name, local, scope));
+        } else if Arc::count(&local.0 .0) == 1 {
             // TODO: hacky and slow
-            if Arc::count(&local.0 .0) == 1 {
-                lock.0 = Some("_".to_string());
-            } else {
-                let prefix = prefix.to_string()
-                    + if self.upvalues.contains(local) {
-                        "_u_"
-                    } else {
-                        ""
-                    };
-                lock.0 = Some(format!("{}{}", prefix, self.counter));
+            lock.0 = Some("_".to_string());
+        } else if let Some(hint) = hint
+            && Formatter::<fmt::Formatter>::is_valid_name(hint.as_bytes())
+            && !self.globals.contains(&hint)
+        {
+            lock.0 = Some(self.unique_name(hint, local));
+        } else {
+            let prefix = prefix.to_string()
+                + if self.upvalues.contains(local) {
+                    "_u_"
+                } else {
+                    ""
+                };
+            let mut name = format!("{}{}", prefix, self.counter);
+            self.counter += 1;
+            while self.names.contains_key(&name) {
+                name = format!("{}{}", prefix, self.counter);
                 self.counter += 1;
             }
+            self.names.insert(name.clone(), local.clone());
+            lock.0 = Some(name);
+        }
+        if let Some(name) = &lock.0 {
+            self.scopes
+                .last_mut()
+                .unwrap()
+                .insert(name.clone(), local.clone());
         }
     }
 
-    fn name_locals(&mut self, block: &mut Block) {
-        for statement in &mut block.0 {
+    // `until` is the condition of the `repeat` that `block` is the body of
+    fn name_locals(&mut self, block: &mut Block, until: Option<&RValue>) {
+        self.scopes.push(FxHashMap::default());
+        for i in 0..block.len() {
+            let (statement, rest) = block.0[i..].split_first_mut().unwrap();
+            let rest = Scope {
+                statements: rest,
+                until,
+            };
             // TODO: traverse_rvalues
             statement.post_traverse_values(&mut |value| -> Option<()> {
                 if let itertools::Either::Right(RValue::Closure(closure)) = value {
                     let mut function = closure.function.lock();
+                    self.scopes.push(FxHashMap::default());
                     for param in &function.parameters {
-                        self.name_local("p", param);
+                        let hint = self
+                            .name_generator
+                            .generate_parameter_name(param, &function.body);
+                        let body = Scope {
+                            statements: &function.body,
+                            until: None,
+                        };
+                        self.name_local("p", param, hint, body);
                     }
-                    self.name_locals(&mut function.body);
+                    self.name_locals(&mut function.body, None);
+                    self.scopes.pop();
                 };
                 None
             });
             match statement {
                 Statement::Assign(assign) if assign.prefix => {
-                    for lvalue in &assign.left {
-                        self.name_local("v", lvalue.as_local().unwrap());
+                    for (i, lvalue) in assign.left.iter().enumerate() {
+                        let hint = if assign.left.len() == assign.right.len() {
+                            self.name_generator
+                                .generate_name(&assign.right[i], self.counter)
+                        } else {
+                            None
+                        };
+                        self.name_local("v", lvalue.as_local().unwrap(), hint, rest);
                     }
                 }
                 Statement::If(r#if) => {
-                    self.name_locals(&mut r#if.then_block.lock());
-                    self.name_locals(&mut r#if.else_block.lock());
+                    self.name_locals(&mut r#if.then_block.lock(), None);
+                    self.name_locals(&mut r#if.else_block.lock(), None);
                 }
                 Statement::While(r#while) => {
-                    self.name_locals(&mut r#while.block.lock());
+                    self.name_locals(&mut r#while.block.lock(), None);
+                }
+                Statement::Do(r#do) => {
+                    self.name_locals(&mut r#do.block.lock(), None);
                 }
                 Statement::Repeat(repeat) => {
-                    self.name_locals(&mut repeat.block.lock());
+                    self.name_locals(&mut repeat.block.lock(), Some(&repeat.condition));
                 }
                 Statement::NumericFor(numeric_for) => {
-                    self.name_local("v", &numeric_for.counter);
-                    self.name_locals(&mut numeric_for.block.lock());
+                    let hint = self.name_generator.generate_numeric_for_name();
+                    let mut block = numeric_for.block.lock();
+                    self.scopes.push(FxHashMap::default());
+                    let body = Scope {
+                        statements: &block,
+                        until: None,
+                    };
+                    self.name_local("v", &numeric_for.counter, hint, body);
+                    self.name_locals(&mut block, None);
+                    self.scopes.pop();
                 }
                 Statement::GenericFor(generic_for) => {
-                    for res_local in &generic_for.res_locals {
-                        self.name_local("v", res_local);
+                    let hints = self.name_generator.generate_generic_for_names(
+                        &generic_for.right,
+                        generic_for.res_locals.len(),
+                    );
+                    let mut block = generic_for.block.lock();
+                    self.scopes.push(FxHashMap::default());
+                    let body = Scope {
+                        statements: &block,
+                        until: None,
+                    };
+                    for (res_local, hint) in generic_for.res_locals.iter().zip(hints) {
+                        self.name_local("v", res_local, hint, body);
                     }
-                    self.name_locals(&mut generic_for.block.lock());
+                    self.name_locals(&mut block, None);
+                    self.scopes.pop();
+                }
+                _ => {}
+            }
+        }
+        self.scopes.pop();
+    }
+
+    // locals that are used without being declared, e.g. in functions that failed to decompile,
+    // would otherwise be printed with a name based on their address
+    fn name_undeclared(&mut self, block: &mut Block) {
+        for statement in &mut block.0 {
+            for local in statement.values() {
+                if local.0 .0.lock().0.is_none() {
+                    let nowhere = Scope {
+                        statements: &[],
+                        until: None,
+                    };
+                    self.name_local("v", local, None, nowhere);
+                }
+            }
+            statement.post_traverse_values(&mut |value| -> Option<()> {
+                if let itertools::Either::Right(RValue::Closure(closure)) = value {
+                    self.name_undeclared(&mut closure.function.lock().body);
+                };
+                None
+            });
+            match statement {
+                Statement::If(r#if) => {
+                    self.name_undeclared(&mut r#if.then_block.lock());
+                    self.name_undeclared(&mut r#if.else_block.lock());
+                }
+                Statement::While(r#while) => {
+                    self.name_undeclared(&mut r#while.block.lock());
+                }
+                Statement::Do(r#do) => {
+                    self.name_undeclared(&mut r#do.block.lock());
+                }
+                Statement::Repeat(repeat) => {
+                    self.name_undeclared(&mut repeat.block.lock());
+                }
+                Statement::NumericFor(numeric_for) => {
+                    self.name_undeclared(&mut numeric_for.block.lock());
+                }
+                Statement::GenericFor(generic_for) => {
+                    self.name_undeclared(&mut generic_for.block.lock());
                 }
                 _ => {}
             }
@@ -101,6 +300,9 @@ impl Namer {
                 Statement::While(r#while) => {
                     self.find_upvalues(&mut r#while.block.lock());
                 }
+                Statement::Do(r#do) => {
+                    self.find_upvalues(&mut r#do.block.lock());
+                }
                 Statement::Repeat(repeat) => {
                     self.find_upvalues(&mut repeat.block.lock());
                 }
@@ -116,12 +318,94 @@ impl Namer {
     }
 }
 
-pub fn name_locals(block: &mut Block, rename: bool) {
+pub fn name_locals(block: &mut Block, rename: bool, name_generator: &dyn NameGenerator) {
     let mut namer = Namer {
         rename,
         counter: 1,
         upvalues: FxHashSet::default(),
+        names: FxHashMap::default(),
+        scopes: vec![FxHashMap::default()],
+        globals: global_names(block).into_iter().collect(),
+        name_generator,
     };
     namer.find_upvalues(block);
-    namer.name_locals(block);
+    namer.name_locals(block, None);
+    namer.name_undeclared(block);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{name_gen::NumberedNameGenerator, Assign, Call, Do, Global, Literal, Local};
+
+    fn local(name: &str) -> RcLocal {
+        RcLocal::new(Local::new(Some(name.to_string())))
+    }
+
+    fn declare(local: &RcLocal, value: f64) -> Statement {
+        let mut assign = Assign::new(
+            vec![local.clone().into()],
+            vec![Literal::Number(value).into()],
+        );
+        assign.prefix = true;
+        assign.into()
+    }
+
+    fn print(locals: &[&RcLocal]) -> Statement {
+        let arguments = locals.iter().map(|&l| l.clone().into()).collect();
+        Call::new(Global::from("print").into(), arguments).into()
+    }
+
+    fn named(statements: Vec<Statement>) -> String {
+        let mut block = Block(statements);
+        name_locals(&mut block, false, &NumberedNameGenerator {});
+        block.to_string()
+    }
+
+    #[test]
+    fn debug_names_can_shadow() {
+        // the outer `x` isn't used while the inner one is in scope
+        let outer = local("x");
+        let inner = local("x");
+        assert_eq!(
+            named(vec![
+                declare(&outer, 1.0),
+                Do::new(Block(vec![declare(&inner, 2.0), print(&[&inner])])).into(),
+                print(&[&outer]),
+            ]),
+            "local x = 1\ndo\n\tlocal x = 2\n\tprint(x)\nend\nprint(x)"
+        );
+    }
+
+    #[test]
+    fn debug_names_renamed_when_shadowed_local_is_used() {
+        let outer = local("x");
+        let inner = local("x");
+        let taken = local("x_2");
+        assert_eq!(
+            named(vec![
+                declare(&outer, 1.0),
+                declare(&taken, 2.0),
+                Do::new(Block(vec![
+                    declare(&inner, 3.0),
+                    print(&[&inner, &outer, &taken]),
+                ]))
+                .into(),
+            ]),
+            "local x = 1\nlocal x_2 = 2\ndo\n\tlocal x_3 = 3\n\tprint(x_3, x, x_2)\nend"
+        );
+    }
+
+    #[test]
+    fn underscore_is_never_renamed() {
+        let outer = local("_");
+        let inner = local("_");
+        assert_eq!(
+            named(vec![
+                declare(&outer, 1.0),
+                Do::new(Block(vec![declare(&inner, 2.0), print(&[&inner, &outer])])).into(),
+            ]),
+            "local _ = 1\ndo\n\tlocal _ = 2\n\tprint(_, _)\nend"
+        );
+    }
 }
@@ -34,6 +34,9 @@ pub fn replace_locals<H: std::hash::BuildHasher>(
             Statement::While(r#while) => {
                 replace_locals(&mut r#while.block.lock(), map);
             }
+            Statement::Do(r#do) => {
+                replace_locals(&mut r#do.block.lock(), map);
+            }
             Statement::Repeat(repeat) => {
                 replace_locals(&mut repeat.block.lock(), map);
             }
@@ -7,13 +7,14 @@ use super::RValue;
 #[derive(Debug, PartialEq, Clone, Default)]
 pub struct Return {
     pub values: Vec<RValue>,
+    pub line: Option<usize>,
 }
 
 has_side_effects!(Return);
 
 impl Return {
     pub fn new(values: Vec<RValue>) -> Self {
-        Self { values }
+        Self { values, line: None }
     }
 }
 
@@ -44,8 +45,10 @@ impl fmt::Display for Return {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         Formatter {
             indentation_level: 0,
-            indentation_mode: Default::default(),
+            options: Default::default(),
             output: f,
+            last_line: None,
+            position: None,
         }
         .format_return(self)
     }
@@ -0,0 +1,461 @@
+use std::fmt;
+
+use indexmap::IndexMap;
+use itertools::Either;
+use parking_lot::Mutex;
+use rustc_hash::{FxHashMap, FxHashSet};
+use triomphe::Arc;
+
+use crate::{
+    formatter::Formatter, name_gen::global_names, Assign, Block, Global, LValue, Literal, Local,
+    LocalRw, MethodCall, RValue, RcLocal, Select, Statement, Traverse, Upvalue,
+};
+
+/// Cleanups for Roblox idioms, run after locals are named
+#[derive(Debug, Clone, Copy)]
+pub struct RobloxPasses {
+    /// Declare services that are fetched more than once with `game:GetService` as
+    /// locals at the top of the script
+    pub hoist_services: bool,
+    /// Inline locals that only hold a `WaitForChild` or `FindFirstChild` result
+    /// which is searched again on the next line
+    pub collapse_find_chains: bool,
+    /// Move property sets up to the `Instance.new` call of the instance they're set on
+    pub group_instance_properties: bool,
+}
+
+impl Default for RobloxPasses {
+    fn default() -> Self {
+        Self {
+            hoist_services: true,
+            collapse_find_chains: true,
+            group_instance_properties: true,
+        }
+    }
+}
+
+impl RobloxPasses {
+    pub fn run(&self, block: &mut Block) {
+        if self.collapse_find_chains {
+            collapse_find_chains(block);
+        }
+        if self.hoist_services {
+            hoist_services(block);
+        }
+        if self.group_instance_properties {
+            group_instance_properties(block);
+        }
+    }
+}
+
+const FIND_METHODS: &[&str] = &["WaitForChild", "FindFirstChild"];
+
+fn nested_blocks(statement: &Statement) -> Vec<&Arc<Mutex<Block>>> {
+    match statement {
+        Statement::If(r#if) => vec![&r#if.then_block, &r#if.else_block],
+        Statement::Do(r#do) => vec![&r#do.block],
+        Statement::While(r#while) => vec![&r#while.block],
+        Statement::Repeat(repeat) => vec![&repeat.block],
+        Statement::NumericFor(numeric_for) => vec![&numeric_for.block],
+        Statement::GenericFor(generic_for) => vec![&generic_for.block],
+        _ => Vec::new(),
+    }
+}
+
+// calls `callback` with every local read or written in `statements`, including in
+// nested blocks and closures, and whether it is written
+fn visit_locals(statements: &[Statement], callback: &mut impl FnMut(&RcLocal, bool)) {
+    fn visit_closures(rvalue: &RValue, callback: &mut impl FnMut(&RcLocal, bool)) {
+        if let RValue::Closure(closure) = rvalue {
+            let function = closure.function.lock();
+            for parameter in &function.parameters {
+                callback(parameter, true);
+            }
+            visit_locals(&function.body, callback);
+        }
+        for rvalue in rvalue.rvalues() {
+            visit_closures(rvalue, callback);
+        }
+    }
+
+    for statement in statements {
+        for local in statement.values_read() {
+            callback(local, false);
+        }
+        for local in statement.values_written() {
+            callback(local, true);
+        }
+        for rvalue in statement.rvalues() {
+            visit_closures(rvalue, callback);
+        }
+        for block in nested_blocks(statement) {
+            visit_locals(&block.lock(), callback);
+        }
+    }
+}
+
+fn is_global(rvalue: &RValue, name: &[u8]) -> bool {
+    matches!(rvalue, RValue::Global(global) if global.0 == name)
+}
+
+fn service_name(rvalue: &RValue) -> Option<&[u8]> {
+    if let RValue::MethodCall(method_call) | RValue::Select(Select::MethodCall(method_call)) =
+        rvalue
+        && method_call.method == "GetService"
+        && is_global(&method_call.value, b"game")
+        && let [RValue::Literal(Literal::String(name))] = &method_call.arguments[..]
+    {
+        Some(name)
+    } else {
+        None
+    }
+}
+
+fn count_services(statements: &[Statement], counts: &mut IndexMap<Vec<u8>, usize>) {
+    fn visit(rvalue: &RValue, counts: &mut IndexMap<Vec<u8>, usize>) {
+        if let Some(name) = service_name(rvalue) {
+            *counts.entry(name.to_vec()).or_default() += 1;
+        }
+        if let RValue::Closure(closure) = rvalue {
+            count_services(&closure.function.lock().body, counts);
+        }
+        for rvalue in rvalue.rvalues() {
+            visit(rvalue, counts);
+        }
+    }
+
+    for statement in statements {
+        for rvalue in statement.rvalues() {
+            visit(rvalue, counts);
+        }
+        for block in nested_blocks(statement) {
+            count_services(&block.lock(), counts);
+        }
+    }
+}
+
+// returns the service locals that were used, so closures can capture them
+fn replace_services(block: &mut Block, services: &FxHashMap<Vec<u8>, RcLocal>) -> Vec<RcLocal> {
+    let mut used = Vec::new();
+    for statement in &mut block.0 {
+        statement.post_traverse_values(&mut |value| -> Option<()> {
+            let Either::Right(rvalue) = value else {
+                return None;
+            };
+            if let Some(local) = service_name(rvalue).and_then(|name| services.get(name)) {
+                if !used.contains(local) {
+                    used.push(local.clone());
+                }
+                *rvalue = RValue::Local(local.clone());
+            } else if let RValue::Closure(closure) = rvalue {
+                for local in replace_services(&mut closure.function.lock().body, services) {
+                    if !closure.upvalues.iter().any(
+                        |upvalue| matches!(upvalue, Upvalue::Copy(l) | Upvalue::Ref(l) if *l == local),
+                    ) {
+                        closure.upvalues.push(Upvalue::Copy(local.clone()));
+                    }
+                    if !used.contains(&local) {
+                        used.push(local);
+                    }
+                }
+            }
+            None
+        });
+        for block in nested_blocks(statement) {
+            for local in replace_services(&mut block.lock(), services) {
+                if !used.contains(&local) {
+                    used.push(local);
+                }
+            }
+        }
+    }
+    used
+}
+
+/// Declares services that are fetched more than once with `game:GetService` at the top
+/// of the script, reusing an existing top level declaration of the service if there is one
+pub fn hoist_services(block: &mut Block) {
+    let mut counts = IndexMap::new();
+    count_services(block, &mut counts);
+    counts.retain(|_, count| *count > 1);
+    if counts.is_empty() {
+        return;
+    }
+
+    let mut writes = FxHashMap::<RcLocal, usize>::default();
+    let mut names = global_names(block).into_iter().collect::<FxHashSet<_>>();
+    visit_locals(block, &mut |local, written| {
+        if written {
+            *writes.entry(local.clone()).or_default() += 1;
+        }
+        if let Some(name) = &local.0 .0.lock().0 {
+            names.insert(name.clone());
+        }
+    });
+
+    // take existing declarations out of the block so that their values aren't replaced
+    let mut declarations = FxHashMap::default();
+    let mut i = 0;
+    while i < block.len() {
+        if let Statement::Assign(assign) = &block[i]
+            && assign.prefix
+            && let ([LValue::Local(local)], [value]) = (&assign.left[..], &assign.right[..])
+            && writes[local] == 1
+            && let Some(name) = service_name(value)
+            && counts.contains_key(name)
+            && !declarations.contains_key(name)
+        {
+            declarations.insert(name.to_vec(), block.0.remove(i));
+        } else {
+            i += 1;
+        }
+    }
+
+    let mut services = FxHashMap::default();
+    let mut hoisted = Vec::with_capacity(counts.len());
+    for name in counts.into_keys() {
+        let declaration = declarations.remove(&name).unwrap_or_else(|| {
+            let base_name = if Formatter::<fmt::Formatter>::is_valid_name(&name) {
+                String::from_utf8_lossy(&name).into_owned()
+            } else {
+                "service".to_string()
+            };
+            let mut local_name = base_name.clone();
+            let mut suffix = 1;
+            while names.contains(&local_name) {
+                suffix += 1;
+                local_name = format!("{}_{}", base_name, suffix);
+            }
+            names.insert(local_name.clone());
+            let local = RcLocal::new(Local::new(Some(local_name)));
+            let mut assign = Assign::new(
+                vec![local.into()],
+                vec![MethodCall::new(
+                    Global::from("game").into(),
+                    "GetService".to_string(),
+                    vec![Literal::String(name.clone()).into()],
+                )
+                .into()],
+            );
+            assign.prefix = true;
+            assign.into()
+        });
+        let Statement::Assign(assign) = &declaration else {
+            unreachable!()
+        };
+        services.insert(name, assign.left[0].as_local().unwrap().clone());
+        hoisted.push(declaration);
+    }
+
+    replace_services(block, &services);
+    block.splice(0..0, hoisted);
+}
+
+fn is_find_call(rvalue: &RValue) -> bool {
+    matches!(
+        rvalue,
+        RValue::MethodCall(method_call) | RValue::Select(Select::MethodCall(method_call))
+            if FIND_METHODS.contains(&method_call.method.as_str())
+    )
+}
+
+// the value the first call in a chain of find calls is made on
+fn chain_receiver(rvalue: &mut RValue) -> Option<&mut RValue> {
+    let (RValue::MethodCall(method_call) | RValue::Select(Select::MethodCall(method_call))) =
+        rvalue
+    else {
+        return None;
+    };
+    if !FIND_METHODS.contains(&method_call.method.as_str()) {
+        None
+    } else if is_find_call(&method_call.value) {
+        chain_receiver(&mut method_call.value)
+    } else {
+        Some(&mut method_call.value)
+    }
+}
+
+// `local a = b:WaitForChild("A")` followed by `local c = a:WaitForChild("C")`,
+// where `a` isn't used anywhere else
+fn collapse(temporary: &mut Statement, next: &mut Statement) -> bool {
+    let (Statement::Assign(temporary), Statement::Assign(next)) = (temporary, next) else {
+        return false;
+    };
+    if !temporary.prefix || !next.prefix || temporary.right.len() != 1 || next.right.len() != 1 {
+        return false;
+    }
+    let [LValue::Local(local)] = &temporary.left[..] else {
+        return false;
+    };
+    // declared and used once
+    if Arc::count(&local.0 .0) != 2 || !is_find_call(&temporary.right[0]) {
+        return false;
+    }
+    if let Some(receiver) = chain_receiver(&mut next.right[0])
+        && receiver.as_local() == Some(local)
+    {
+        *receiver = temporary.right.pop().unwrap();
+        true
+    } else {
+        false
+    }
+}
+
+/// Inlines locals that only hold the result of a `WaitForChild` or `FindFirstChild` call
+/// that is searched again on the next line, turning them into a single chain
+pub fn collapse_find_chains(block: &mut Block) {
+    for statement in &mut block.0 {
+        statement.post_traverse_values(&mut |value| -> Option<()> {
+            if let Either::Right(RValue::Closure(closure)) = value {
+                collapse_find_chains(&mut closure.function.lock().body)
+            };
+            None
+        });
+        for block in nested_blocks(statement) {
+            collapse_find_chains(&mut block.lock());
+        }
+    }
+    let mut i = 0;
+    while i + 1 < block.len() {
+        let (temporary, next) = block.0.split_at_mut(i + 1);
+        if collapse(&mut temporary[i], &mut next[0]) {
+            block.0.remove(i);
+        } else {
+            i += 1;
+        }
+    }
+}
+
+fn instance_local(statement: &Statement) -> Option<&RcLocal> {
+    if let Statement::Assign(assign) = statement
+        && assign.prefix
+        && let ([LValue::Local(local)], [RValue::Call(call) | RValue::Select(Select::Call(call))]) =
+            (&assign.left[..], &assign.right[..])
+        && let RValue::Index(index) = &*call.value
+        && is_global(&index.left, b"Instance")
+        && matches!(&*index.right, RValue::Literal(Literal::String(new)) if new == b"new")
+    {
+        Some(local)
+    } else {
+        None
+    }
+}
+
+fn property_set<'a>(
+    statement: &'a Statement,
+    instance: &RcLocal,
+) -> Option<(&'a [u8], &'a RValue)> {
+    if let Statement::Assign(assign) = statement
+        && !assign.prefix
+        && let ([LValue::Index(index)], [value]) = (&assign.left[..], &assign.right[..])
+        && matches!(&*index.left, RValue::Local(local) if local == instance)
+        && let RValue::Literal(Literal::String(property)) = &*index.right
+    {
+        Some((property, value))
+    } else {
+        None
+    }
+}
+
+// whether `rvalue` evaluates to the same thing no matter where it is moved,
+// as long as it isn't moved before a declaration of a local it reads
+fn is_constant(rvalue: &RValue, writes: &FxHashMap<RcLocal, usize>) -> bool {
+    fn is_enum(rvalue: &RValue) -> bool {
+        match rvalue {
+            RValue::Global(global) => global.0 == b"Enum",
+            RValue::Index(index) => {
+                matches!(&*index.right, RValue::Literal(Literal::String(_))) && is_enum(&index.left)
+            }
+            _ => false,
+        }
+    }
+
+    match rvalue {
+        RValue::Literal(_) => true,
+        RValue::Local(local) => writes.get(local) == Some(&1),
+        RValue::Index(_) => is_enum(rvalue),
+        _ => false,
+    }
+}
+
+fn group(block: &mut Block, writes: &FxHashMap<RcLocal, usize>) {
+    let mut i = 0;
+    while i < block.len() {
+        let Some(instance) = instance_local(&block[i]).cloned() else {
+            i += 1;
+            continue;
+        };
+        // the statements in `i..end` set properties of `instance`
+        let mut end = i + 1;
+        for j in i + 1..block.len() {
+            match property_set(&block[j], &instance) {
+                // setting the parent can fire events, so it must stay after the rest
+                Some((property, value)) if property != b"Parent" => {
+                    if j != end {
+                        let mut between = FxHashSet::default();
+                        visit_locals(&block[end..j], &mut |local, written| {
+                            if written {
+                                between.insert(local.clone());
+                            }
+                        });
+                        if !is_constant(value, writes)
+                            || value.values_read().iter().any(|l| between.contains(*l))
+                        {
+                            break;
+                        }
+                        let statement = block.0.remove(j);
+                        block.0.insert(end, statement);
+                    }
+                    end += 1;
+                }
+                _ => {
+                    let mut references = false;
+                    visit_locals(&block[j..=j], &mut |local, _| {
+                        references |= *local == instance
+                    });
+                    if references
+                        || matches!(
+                            block[j],
+                            Statement::Goto(_)
+                                | Statement::Label(_)
+                                | Statement::Return(_)
+                                | Statement::Break(_)
+                                | Statement::Continue(_)
+                        )
+                    {
+                        break;
+                    }
+                }
+            }
+        }
+        i = end;
+    }
+}
+
+fn group_block(block: &mut Block, writes: &FxHashMap<RcLocal, usize>) {
+    for statement in &mut block.0 {
+        statement.post_traverse_values(&mut |value| -> Option<()> {
+            if let Either::Right(RValue::Closure(closure)) = value {
+                group_block(&mut closure.function.lock().body, writes)
+            };
+            None
+        });
+        for block in nested_blocks(statement) {
+            group_block(&mut block.lock(), writes);
+        }
+    }
+    group(block, writes);
+}
+
+/// Moves property sets of an instance created with `Instance.new` up to where it is
+/// created when nothing in between can observe the difference. Setting `Parent` is
+/// never moved.
+pub fn group_instance_properties(block: &mut Block) {
+    let mut writes = FxHashMap::<RcLocal, usize>::default();
+    visit_locals(block, &mut |local, written| {
+        if written {
+            *writes.entry(local.clone()).or_default() += 1;
+        }
+    });
+    group_block(block, &writes);
+}
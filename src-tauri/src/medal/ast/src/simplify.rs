@@ -0,0 +1,360 @@
+use itertools::Either;
+use triomphe::Arc;
+
+use crate::{
+    Binary, BinaryOperation, Block, Do, If, Literal, RValue, SideEffects, Statement, Traverse,
+    Unary, UnaryOperation, While,
+};
+
+fn fold_arithmetic(left: f64, right: f64, operation: BinaryOperation) -> Option<f64> {
+    let result = match operation {
+        BinaryOperation::Add => left + right,
+        BinaryOperation::Sub => left - right,
+        BinaryOperation::Mul => left * right,
+        BinaryOperation::Div => left / right,
+        BinaryOperation::IDiv => (left / right).floor(),
+        BinaryOperation::Mod => left - (left / right).floor() * right,
+        BinaryOperation::Pow => left.powf(right),
+        _ => return None,
+    };
+    // `1 / 0` is more readable than what infinity and nan are formatted as
+    result.is_finite().then_some(result)
+}
+
+fn fold_binary(binary: &mut Binary) -> Option<RValue> {
+    let RValue::Literal(left) = &*binary.left else {
+        return None;
+    };
+    match binary.operation {
+        // the right side is only evaluated if it is the result
+        BinaryOperation::And | BinaryOperation::Or => {
+            if left.is_truthy() == (binary.operation == BinaryOperation::And) {
+                Some(std::mem::replace(&mut *binary.right, Literal::Nil.into()))
+            } else {
+                Some(left.clone().into())
+            }
+        }
+        operation => {
+            let RValue::Literal(right) = &*binary.right else {
+                return None;
+            };
+            let literal = match (left, right, operation) {
+                (_, _, BinaryOperation::Equal) => Literal::Boolean(left == right),
+                (_, _, BinaryOperation::NotEqual) => Literal::Boolean(left != right),
+                (Literal::Number(left), Literal::Number(right), operation)
+                    if operation.is_comparator() =>
+                {
+                    Literal::Boolean(match operation {
+                        BinaryOperation::LessThan => left < right,
+                        BinaryOperation::LessThanOrEqual => left <= right,
+                        BinaryOperation::GreaterThan => left > right,
+                        BinaryOperation::GreaterThanOrEqual => left >= right,
+                        _ => unreachable!(),
+                    })
+                }
+                (Literal::Number(left), Literal::Number(right), operation) => {
+                    Literal::Number(fold_arithmetic(*left, *right, operation)?)
+                }
+                // numbers aren't folded since how they're converted to strings depends on the VM
+                (Literal::String(left), Literal::String(right), BinaryOperation::Concat) => {
+                    Literal::String([left.as_slice(), right].concat())
+                }
+                _ => return None,
+            };
+            Some(literal.into())
+        }
+    }
+}
+
+fn fold_unary(unary: &Unary) -> Option<RValue> {
+    let RValue::Literal(literal) = &*unary.value else {
+        return None;
+    };
+    let literal = match (literal, unary.operation) {
+        (literal, UnaryOperation::Not) => Literal::Boolean(!literal.is_truthy()),
+        // -0 is kept as is
+        (&Literal::Number(value), UnaryOperation::Negate) if value != 0.0 => {
+            Literal::Number(-value)
+        }
+        (Literal::String(value), UnaryOperation::Length) => Literal::Number(value.len() as f64),
+        _ => return None,
+    };
+    Some(literal.into())
+}
+
+fn fold(rvalue: &mut RValue) -> Option<RValue> {
+    match rvalue {
+        RValue::Binary(binary) => fold_binary(binary),
+        RValue::Unary(unary) => fold_unary(unary),
+        RValue::IfExpression(if_expression) => match &*if_expression.condition {
+            RValue::Literal(literal) if literal.is_truthy() => Some(std::mem::replace(
+                &mut *if_expression.then_value,
+                Literal::Nil.into(),
+            )),
+            RValue::Literal(_) => Some(std::mem::replace(
+                &mut *if_expression.else_value,
+                Literal::Nil.into(),
+            )),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// a local declaration that is never used and can't have side effects
+fn is_dead(statement: &Statement) -> bool {
+    match statement {
+        Statement::Empty(_) => true,
+        Statement::Assign(assign) => {
+            assign.prefix
+                && assign
+                    .left
+                    .iter()
+                    .all(|l| l.as_local().is_some_and(|l| Arc::count(&l.0 .0) == 1))
+                // functions are kept even if unused since they're likely of interest
+                && assign
+                    .right
+                    .iter()
+                    .all(|r| !r.has_side_effects() && !matches!(r, RValue::Closure(_)))
+        }
+        _ => false,
+    }
+}
+
+/// Folds operations on literals and removes code that can never run or has no effect:
+/// branches of ifs with constant conditions, `while false` loops, unused local
+/// declarations without side effects and statements after `return`, `break` and `continue`
+pub fn simplify(block: &mut Block) {
+    for statement in &mut block.0 {
+        statement.post_traverse_values(&mut |value| -> Option<()> {
+            if let Either::Right(rvalue) = value {
+                if let Some(folded) = fold(rvalue) {
+                    *rvalue = folded;
+                } else if let RValue::Closure(closure) = rvalue {
+                    simplify(&mut closure.function.lock().body);
+                }
+            };
+            None
+        });
+        match statement {
+            Statement::If(r#if) => {
+                simplify(&mut r#if.then_block.lock());
+                simplify(&mut r#if.else_block.lock());
+            }
+            Statement::While(r#while) => {
+                simplify(&mut r#while.block.lock());
+            }
+            Statement::Do(r#do) => {
+                simplify(&mut r#do.block.lock());
+            }
+            Statement::Repeat(repeat) => {
+                simplify(&mut repeat.block.lock());
+            }
+            Statement::NumericFor(numeric_for) => {
+                simplify(&mut numeric_for.block.lock());
+            }
+            Statement::GenericFor(generic_for) => {
+                simplify(&mut generic_for.block.lock());
+            }
+            _ => {}
+        }
+    }
+
+    let mut statements = Vec::with_capacity(block.len());
+    for statement in std::mem::take(&mut block.0) {
+        match statement {
+            Statement::If(If {
+                condition: RValue::Literal(condition),
+                then_block,
+                else_block,
+                ..
+            }) => {
+                let taken = if condition.is_truthy() {
+                    then_block
+                } else {
+                    else_block
+                };
+                let taken = std::mem::take(&mut *taken.lock());
+                // locals and labels declared in the block could shadow the ones after it,
+                // so it keeps its own scope
+                if taken.iter().any(|s| {
+                    matches!(s, Statement::Assign(assign) if assign.prefix)
+                        || matches!(s, Statement::Label(_))
+                }) {
+                    statements.push(Do::new(taken).into());
+                } else {
+                    statements.extend(taken.0);
+                }
+            }
+            Statement::While(While {
+                condition: RValue::Literal(condition),
+                ..
+            }) if !condition.is_truthy() => {}
+            statement => statements.push(statement),
+        }
+    }
+    // labels could still be jumped to
+    if let Some(end) = statements.iter().position(|s| {
+        matches!(
+            s,
+            Statement::Return(_) | Statement::Break(_) | Statement::Continue(_)
+        )
+    }) && !statements[end..]
+        .iter()
+        .any(|s| matches!(s, Statement::Label(_)))
+    {
+        statements.truncate(end + 1);
+    }
+    // in reverse so that locals only used by removed declarations are removed too
+    for i in (0..statements.len()).rev() {
+        if is_dead(&statements[i]) {
+            statements.remove(i);
+        }
+    }
+    block.0 = statements;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Assign, Call, Global, Label, Local, RcLocal, Return};
+
+    fn number(value: f64) -> RValue {
+        Literal::Number(value).into()
+    }
+
+    fn string(value: &str) -> RValue {
+        Literal::String(value.as_bytes().to_vec()).into()
+    }
+
+    fn binary(left: RValue, right: RValue, operation: BinaryOperation) -> RValue {
+        Binary::new(left, right, operation).into()
+    }
+
+    fn local(name: &str) -> RcLocal {
+        RcLocal::new(Local::new(Some(name.to_string())))
+    }
+
+    fn declare(local: &RcLocal, value: RValue) -> Statement {
+        let mut assign = Assign::new(vec![local.clone().into()], vec![value]);
+        assign.prefix = true;
+        assign.into()
+    }
+
+    fn print(values: Vec<RValue>) -> Statement {
+        Call::new(Global::from("print").into(), values).into()
+    }
+
+    fn simplified(statements: Vec<Statement>) -> String {
+        let mut block = Block(statements);
+        simplify(&mut block);
+        block.to_string()
+    }
+
+    #[test]
+    fn folds_constants() {
+        let f = || RValue::from(Global::from("f"));
+        assert_eq!(
+            simplified(vec![print(vec![
+                binary(
+                    number(1.0),
+                    binary(number(2.0), number(3.0), BinaryOperation::Mul),
+                    BinaryOperation::Add
+                ),
+                binary(string("a"), string("b"), BinaryOperation::Concat),
+                binary(number(1.0), number(2.0), BinaryOperation::LessThan),
+                Unary::new(Literal::Nil.into(), UnaryOperation::Not).into(),
+                binary(Literal::Boolean(true).into(), f(), BinaryOperation::And),
+                binary(Literal::Boolean(false).into(), f(), BinaryOperation::And),
+            ])]),
+            "print(7, \"ab\", true, true, f, false)"
+        );
+        // infinity isn't folded, and neither are numbers concatenated to strings
+        assert_eq!(
+            simplified(vec![print(vec![
+                binary(number(1.0), number(0.0), BinaryOperation::Div),
+                binary(string("a"), number(1.0), BinaryOperation::Concat),
+            ])]),
+            "print(1 / 0, \"a\" .. 1)"
+        );
+    }
+
+    #[test]
+    fn prunes_constant_branches() {
+        let r#if = |condition: bool| -> Statement {
+            If::new(
+                Literal::Boolean(condition).into(),
+                Block(vec![print(vec![number(1.0)])]),
+                Block(vec![print(vec![number(2.0)])]),
+            )
+            .into()
+        };
+        assert_eq!(simplified(vec![r#if(true)]), "print(1)");
+        assert_eq!(simplified(vec![r#if(false)]), "print(2)");
+        let r#while = While::new(
+            Literal::Boolean(false).into(),
+            Block(vec![print(vec![number(3.0)])]),
+        );
+        assert_eq!(simplified(vec![r#while.into()]), "");
+    }
+
+    #[test]
+    fn taken_branch_keeps_its_scope() {
+        // local x = 1 if true then local x = 2 print(x) end print(x)
+        let outer = local("x");
+        let inner = local("x");
+        let r#if = If::new(
+            Literal::Boolean(true).into(),
+            Block(vec![
+                declare(&inner, number(2.0)),
+                print(vec![inner.clone().into()]),
+            ]),
+            Block::default(),
+        );
+        assert_eq!(
+            simplified(vec![
+                declare(&outer, number(1.0)),
+                r#if.into(),
+                print(vec![outer.clone().into()]),
+            ]),
+            "local x = 1\ndo\n\tlocal x = 2\n\tprint(x)\nend\nprint(x)"
+        );
+    }
+
+    #[test]
+    fn removes_unused_declarations() {
+        // the handles are dropped so that the declarations hold the only references
+        let statements = {
+            let a = local("a");
+            let b = local("b");
+            let c = local("c");
+            vec![
+                declare(&a, number(1.0)),
+                declare(&b, a.clone().into()),
+                declare(&c, Call::new(Global::from("f").into(), Vec::new()).into()),
+            ]
+        };
+        assert_eq!(simplified(statements), "local c = f()");
+    }
+
+    #[test]
+    fn truncates_after_return() {
+        assert_eq!(
+            simplified(vec![
+                print(vec![number(1.0)]),
+                Return::new(Vec::new()).into(),
+                print(vec![number(2.0)]),
+            ]),
+            "print(1)\nreturn"
+        );
+        // a label after the return could still be jumped to
+        assert_eq!(
+            simplified(vec![
+                Return::new(Vec::new()).into(),
+                print(vec![number(2.0)]),
+                Label("label".to_string()).into(),
+            ]),
+            "return\nprint(2)\n::label::"
+        );
+    }
+}
@@ -1,8 +1,10 @@
 use crate::{
-    formatter::Formatter, Literal, LocalRw, RValue, RcLocal, Reduce, SideEffects, Traverse,
+    formatter::Formatter,
+    type_system::{Infer, TypeSystem},
+    Literal, LocalRw, RValue, RcLocal, Reduce, SideEffects, Traverse, Type,
 };
 
-use std::{fmt, iter};
+use std::{collections::BTreeMap, fmt, iter};
 
 #[derive(Debug, Clone, PartialEq, Default)]
 pub struct Table(pub Vec<(Option<RValue>, RValue)>);
@@ -22,39 +24,41 @@ impl Reduce for Table {
     }
 }
 
-/*impl Infer for Table {
-    fn infer<'a: 'b, 'b>(&'a mut self, system: &mut TypeSystem<'b>) -> Type {
-        let elements: BTreeSet<_> = self
-            .0
-            .iter_mut()
-            .map(|(f, v)| (f.clone(), v.infer(system)))
-            .collect();
-        let elements: BTreeSet<_> = elements
-            .iter()
-            .filter(|(f, t)| {
-                f.is_some() || !elements.iter().any(|(_, x)| t != x && t.is_subtype_of(x))
-            })
-            .cloned()
-            .collect();
-        let (elements, fields): (BTreeSet<_>, BTreeMap<_, _>) =
-            elements.into_iter().partition_map(|(f, t)| match f {
-                None => Either::Left(t),
-                Some(f) => Either::Right((f, t)),
+impl Infer for Table {
+    fn infer(&self, system: &TypeSystem) -> Type {
+        let mut indexer: Option<(Type, Type)> = None;
+        let mut fields = BTreeMap::new();
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            // the last value can be any number of values
+            let value_type = if i + 1 == self.0.len() && matches!(value, RValue::Select(_)) {
+                Type::Any
+            } else {
+                value.infer(system)
+            };
+            let key_type = match key {
+                Some(RValue::Literal(Literal::String(field)))
+                    if Formatter::<fmt::Formatter>::is_valid_name(field) =>
+                {
+                    fields.insert(String::from_utf8_lossy(field).into_owned(), value_type);
+                    continue;
+                }
+                Some(key) => key.infer(system),
+                None => Type::Number,
+            };
+            indexer = Some(match indexer {
+                Some((indexer_type, element_type)) => {
+                    (indexer_type.union(key_type), element_type.union(value_type))
+                }
+                None => (key_type, value_type),
             });
+        }
 
         Type::Table {
-            indexer: Box::new((
-                Type::Any,
-                if elements.len() > 1 {
-                    Type::Union(elements)
-                } else {
-                    elements.into_iter().next().unwrap_or(Type::Any)
-                },
-            )),
+            indexer: indexer.map(Box::new),
             fields,
         }
     }
-}*/
+}
 
 impl LocalRw for Table {
     fn values_read(&self) -> Vec<&RcLocal> {
@@ -119,8 +123,10 @@ impl fmt::Display for Table {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         Formatter {
             indentation_level: 0,
-            indentation_mode: Default::default(),
+            options: Default::default(),
             output: f,
+            last_line: None,
+            position: None,
         }
         .format_table(self)
     }
@@ -1,4 +1,6 @@
-use crate::{Block, RcLocal};
+use crate::{
+    BinaryOperation, Block, Function, LValue, Literal, RValue, RcLocal, Statement, Traverse,
+};
 use itertools::Itertools;
 use std::{
     borrow::Cow,
@@ -6,6 +8,8 @@ use std::{
     fmt::{Display, Formatter},
 };
 
+use rustc_hash::FxHashMap;
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone)]
 pub enum Type {
     Any,
@@ -14,7 +18,7 @@ pub enum Type {
     Number,
     String,
     Table {
-        indexer: Box<(Type, Type)>,
+        indexer: Option<Box<(Type, Type)>>,
         fields: BTreeMap<String, Type>,
     },
     Function(Vec<Type>, Vec<Type>),
@@ -23,6 +27,10 @@ pub enum Type {
     Intersection(BTreeSet<Type>),
     VarArg,
     Vector,
+    Thread,
+    Buffer,
+    // named userdata types (i.e. Vector3) come from the host, unnamed userdata is just any
+    Userdata(String),
 }
 
 impl Type {
@@ -30,26 +38,25 @@ impl Type {
         match t {
             Self::Any => true,
             Self::Table {
-                box indexer,
-                fields,
-            } => {
-                let t_fields = fields;
-                let (indexer_type, element_type) = indexer;
-
-                match self {
-                    Self::Table {
-                        box indexer,
-                        fields,
-                    } if indexer.0.is_subtype_of(indexer_type)
-                        && indexer.1.is_subtype_of(element_type) =>
-                    {
-                        t_fields
-                            .keys()
-                            .all(|k| fields[k].is_subtype_of(&t_fields[k]))
-                    }
-                    _ => false,
+                indexer: t_indexer,
+                fields: t_fields,
+            } => match self {
+                Self::Table { indexer, fields }
+                    if match (indexer, t_indexer) {
+                        (Some(indexer), Some(t_indexer)) => {
+                            indexer.0.is_subtype_of(&t_indexer.0)
+                                && indexer.1.is_subtype_of(&t_indexer.1)
+                        }
+                        (_, None) => true,
+                        (None, Some(_)) => false,
+                    } =>
+                {
+                    t_fields
+                        .iter()
+                        .all(|(k, t)| fields.get(k).is_some_and(|field| field.is_subtype_of(t)))
                 }
-            }
+                _ => false,
+            },
             Self::Union(union) => match self {
                 Self::Union(u) => union.iter().all(|t| u.contains(t)),
                 _ => union.contains(self),
@@ -58,6 +65,72 @@ impl Type {
         }
     }
 
+    /// The smallest type both `self` and `other` are subtypes of
+    pub fn union(self, other: Self) -> Self {
+        match (self, other) {
+            (a, b) if a == b => a,
+            (Self::Any, _) | (_, Self::Any) => Self::Any,
+            (
+                Self::Table {
+                    indexer: a_indexer,
+                    fields: mut a_fields,
+                },
+                Self::Table {
+                    indexer: b_indexer,
+                    fields: b_fields,
+                },
+            ) => {
+                let indexer = match (a_indexer, b_indexer) {
+                    (Some(box (a_key, a_value)), Some(box (b_key, b_value))) => {
+                        Some(Box::new((a_key.union(b_key), a_value.union(b_value))))
+                    }
+                    (indexer, None) | (None, indexer) => indexer,
+                };
+                for (field, b_type) in b_fields {
+                    let r#type = match a_fields.remove(&field) {
+                        Some(a_type) => a_type.union(b_type),
+                        None => b_type,
+                    };
+                    a_fields.insert(field, r#type);
+                }
+                Self::Table {
+                    indexer,
+                    fields: a_fields,
+                }
+            }
+            (Self::Nil, Self::Optional(r#type)) | (Self::Optional(r#type), Self::Nil) => {
+                Self::Optional(r#type)
+            }
+            (Self::Optional(a), Self::Optional(b)) => Self::Optional(Box::new(a.union(*b))),
+            (Self::Nil, r#type) | (r#type, Self::Nil) => Self::Optional(Box::new(r#type)),
+            (Self::Optional(box a), b) | (b, Self::Optional(box a)) => {
+                Self::Optional(Box::new(a.union(b)))
+            }
+            (Self::Union(mut a), Self::Union(b)) => {
+                a.extend(b);
+                Self::Union(a)
+            }
+            (Self::Union(mut union), r#type) | (r#type, Self::Union(mut union)) => {
+                union.insert(r#type);
+                Self::Union(union)
+            }
+            (a, b) => Self::Union(BTreeSet::from([a, b])),
+        }
+    }
+
+    // whether printing `self` as an annotation tells the reader anything
+    fn is_informative(&self) -> bool {
+        match self {
+            Self::Any | Self::Nil | Self::VarArg | Self::Function(..) => false,
+            Self::Table { indexer, fields } => indexer.is_some() || !fields.is_empty(),
+            Self::Optional(r#type) => r#type.is_informative(),
+            Self::Union(types) | Self::Intersection(types) => {
+                !types.contains(&Self::Any) && !types.contains(&Self::VarArg)
+            }
+            _ => true,
+        }
+    }
+
     pub fn precedence(&self) -> usize {
         match self {
             Self::Any => 0,
@@ -72,6 +145,9 @@ impl Type {
             Self::Intersection(_) => 2,
             Self::VarArg => 0,
             Self::Vector => 0,
+            Self::Thread => 0,
+            Self::Buffer => 0,
+            Self::Userdata(_) => 0,
         }
     }
 }
@@ -88,19 +164,23 @@ impl Display for Type {
                 Type::Number => Cow::Borrowed("number"),
                 Type::String => Cow::Borrowed("string"),
                 Type::Table { indexer, fields } => {
-                    let (indexer_type, element_type) = indexer.as_ref();
-
-                    Cow::Owned(format!(
-                        "{{{}{}{}}}",
+                    let indexer = indexer.as_deref().map(|(indexer_type, element_type)| {
                         if indexer_type == &Type::Number && fields.is_empty() {
                             element_type.to_string()
                         } else {
                             format!("[{}]: {}", indexer_type, element_type)
-                        },
-                        (!fields.is_empty()).then_some(", ").unwrap_or_default(),
-                        fields
-                            .iter()
-                            .map(|(field, r#type)| { format!("{}: {}", field, r#type) })
+                        }
+                    });
+
+                    Cow::Owned(format!(
+                        "{{{}}}",
+                        indexer
+                            .into_iter()
+                            .chain(
+                                fields
+                                    .iter()
+                                    .map(|(field, r#type)| format!("{}: {}", field, r#type))
+                            )
                             .join(", ")
                     ))
                 }
@@ -115,6 +195,9 @@ impl Display for Type {
                         codomain.iter().join(", ")
                     }
                 )),
+                Type::Optional(r#type) if r#type.precedence() > 0 => {
+                    Cow::Owned(format!("({})?", r#type))
+                }
                 Type::Optional(r#type) => Cow::Owned(format!("{}?", r#type)),
                 Type::Union(types) => {
                     Cow::Owned(types.iter().join(" | "))
@@ -122,85 +205,390 @@ impl Display for Type {
                 Type::Intersection(types) => {
                     Cow::Owned(types.iter().join(" & "))
                 }
-                Type::VarArg => Cow::Borrowed("..."),
+                Type::VarArg => Cow::Borrowed("...any"),
                 Type::Vector => Cow::Borrowed("vector"),
+                Type::Thread => Cow::Borrowed("thread"),
+                Type::Buffer => Cow::Borrowed("buffer"),
+                Type::Userdata(name) => Cow::Borrowed(name.as_str()),
             }
         )
     }
 }
 
-pub struct TypeSystem<'a> {
-    // TODO: use hash map?
-    annotations: BTreeMap<&'a RcLocal, &'a mut Type>,
+// types of locals can depend on locals that are inferred later, i.e. upvalues, so
+// inference is repeated until nothing changes or it ran this many times
+const MAX_PASSES: usize = 4;
+
+fn field_name(rvalue: &RValue) -> Option<&str> {
+    match rvalue {
+        RValue::Literal(Literal::String(field))
+            if crate::formatter::Formatter::<std::fmt::Formatter>::is_valid_name(field) =>
+        {
+            std::str::from_utf8(field).ok()
+        }
+        _ => None,
+    }
+}
+
+fn table_with_field(field: &str, r#type: Type) -> Type {
+    Type::Table {
+        indexer: None,
+        fields: BTreeMap::from([(field.to_string(), r#type)]),
+    }
+}
+
+#[derive(Default)]
+pub struct TypeSystem {
+    // the types locals were inferred to have, locals that aren't in here are `any`
+    types: FxHashMap<RcLocal, Type>,
+    // the types of the values assigned to locals in the current pass
+    definitions: FxHashMap<RcLocal, Vec<Type>>,
+    // the types locals are used as in the current pass, i.e. `number` for `x + 1`
+    uses: FxHashMap<RcLocal, Vec<Type>>,
 }
 
-impl<'a> TypeSystem<'a> {
-    pub fn analyze(block: &'a mut Block) {
-        let mut system = Self {
-            annotations: BTreeMap::new(),
-        };
-
-        system.analyze_block(block);
-    }
-
-    pub fn analyze_block(&mut self, _block: &'a mut Block) -> Vec<Type> {
-        todo!()
-        // let mut return_values = Vec::new();
-
-        // for statement in &mut block.0 {
-        //     match statement {
-        //         /*Statement::Assign(assign) => {
-        //             for ((lvalue, annotation), rvalue) in
-        //                 assign.left.iter_mut().zip(assign.right.iter_mut())
-        //             {
-        //                 let r#type = rvalue.infer(self);
-
-        //                 if let LValue::Local(local) = lvalue {
-        //                     if let Some(annotation) = self.annotations.get_mut(local) {
-        //                         if let Type::Union(types) = annotation {
-        //                             types.insert(r#type);
-        //                         } else {
-        //                             let mut types = BTreeSet::new();
-
-        //                             types.insert(annotation.clone());
-        //                             types.insert(r#type);
-
-        //                             **annotation = Type::Union(types);
-        //                         }
-        //                     } else {
-        //                         *annotation = Some(r#type);
-        //                         self.annotations.insert(local, annotation.as_mut().unwrap());
-        //                     }
-        //                 }
-        //             }
-        //         }*/
-        //         Statement::If(r#if) => {
-        //             self.analyze_block(&mut r#if.then_block);
-        //             self.analyze_block(&mut r#if.else_block);
-        //         }
-        //         // TODO: repeat and for loops
-        //         Statement::While(r#while) => {
-        //             self.analyze_block(&mut r#while.block.lock());
-        //         }
-        //         Statement::Return(r#return) => {
-        //             return_values.extend(r#return.values.iter_mut().map(|v| v.infer(self)));
-        //         }
-        //         // TODO: numericfor, genericfor
-        //         _ => {}
-        //     }
-        // }
-
-        // return_values
+impl TypeSystem {
+    /// Infers the types of locals, parameters and return values in `block` and the
+    /// functions in it, and stores them in the AST so they're printed as annotations
+    pub fn analyze(block: &mut Block) {
+        let mut system = Self::default();
+        for _ in 0..MAX_PASSES {
+            system.analyze_block(block);
+            let types = system.resolve();
+            if types == system.types {
+                break;
+            }
+            system.types = types;
+        }
+        system.annotate_block(block);
+    }
+
+    pub fn analyze_block(&mut self, block: &Block) {
+        for statement in block.iter() {
+            match statement {
+                Statement::Assign(assign) => {
+                    for (i, lvalue) in assign.left.iter().enumerate() {
+                        let r#type = if assign.right.is_empty() {
+                            Type::Nil
+                        } else if i + 1 < assign.right.len()
+                            || assign.left.len() == assign.right.len()
+                        {
+                            self.infer_value(&assign.right[i])
+                        } else {
+                            // one of the values a call or `...` evaluates to
+                            Type::Any
+                        };
+                        self.analyze_lvalue(lvalue, r#type);
+                    }
+                }
+                Statement::CompoundAssign(compound_assign) => {
+                    let r#type = match compound_assign.operation {
+                        BinaryOperation::Concat => Type::String,
+                        _ => Type::Number,
+                    };
+                    if let RValue::Local(local) = &compound_assign.right {
+                        self.use_as(local, r#type.clone());
+                    }
+                    if let LValue::Local(local) = &compound_assign.left {
+                        self.use_as(local, r#type.clone());
+                    }
+                    self.analyze_lvalue(&compound_assign.left, r#type);
+                }
+                Statement::NumericFor(numeric_for) => {
+                    self.define(&numeric_for.counter, Type::Number);
+                }
+                _ => {}
+            }
+            for rvalue in statement.rvalues() {
+                self.analyze_value(rvalue);
+            }
+            match statement {
+                Statement::If(r#if) => {
+                    self.analyze_block(&r#if.then_block.lock());
+                    self.analyze_block(&r#if.else_block.lock());
+                }
+                Statement::While(r#while) => {
+                    self.analyze_block(&r#while.block.lock());
+                }
+                Statement::Do(r#do) => {
+                    self.analyze_block(&r#do.block.lock());
+                }
+                Statement::Repeat(repeat) => {
+                    self.analyze_block(&repeat.block.lock());
+                }
+                Statement::NumericFor(numeric_for) => {
+                    self.analyze_block(&numeric_for.block.lock());
+                }
+                Statement::GenericFor(generic_for) => {
+                    self.analyze_block(&generic_for.block.lock());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    // `r#type` is the type of the value assigned to `lvalue`
+    fn analyze_lvalue(&mut self, lvalue: &LValue, r#type: Type) {
+        match lvalue {
+            LValue::Local(local) => self.define(local, r#type),
+            LValue::Index(index) => {
+                if let RValue::Local(local) = &*index.left
+                    && let Some(field) = field_name(&index.right)
+                {
+                    self.use_as(local, table_with_field(field, r#type));
+                }
+                self.analyze_value(&index.left);
+                self.analyze_value(&index.right);
+            }
+            LValue::Global(_) => {}
+        }
+    }
+
+    fn analyze_value(&mut self, rvalue: &RValue) {
+        match rvalue {
+            RValue::Binary(binary) => {
+                // userdata like Vector3 can be multiplied and divided by numbers, so only
+                // operations that need both sides to be numbers tell us anything
+                let r#type = match binary.operation {
+                    BinaryOperation::Add
+                    | BinaryOperation::Sub
+                    | BinaryOperation::Mod
+                    | BinaryOperation::Pow => Some(Type::Number),
+                    BinaryOperation::Concat => Some(Type::String),
+                    _ => None,
+                };
+                if let Some(r#type) = r#type {
+                    for (operand, other) in
+                        [(&binary.left, &binary.right), (&binary.right, &binary.left)]
+                    {
+                        if let RValue::Local(local) = &**operand
+                            && (r#type == Type::String || other.infer(self) == Type::Number)
+                        {
+                            self.use_as(local, r#type.clone());
+                        }
+                    }
+                }
+            }
+            RValue::Index(index) => {
+                if let RValue::Local(local) = &*index.left
+                    && let Some(field) = field_name(&index.right)
+                {
+                    self.use_as(local, table_with_field(field, Type::Any));
+                }
+            }
+            RValue::Closure(closure) => {
+                self.analyze_block(&closure.function.lock().body);
+            }
+            _ => {}
+        }
+        for rvalue in rvalue.rvalues() {
+            self.analyze_value(rvalue);
+        }
+    }
+
+    fn infer_value(&self, rvalue: &RValue) -> Type {
+        match rvalue.infer(self) {
+            Type::VarArg => Type::Any,
+            r#type => r#type,
+        }
+    }
+
+    fn define(&mut self, local: &RcLocal, r#type: Type) {
+        self.definitions
+            .entry(local.clone())
+            .or_default()
+            .push(r#type);
+    }
+
+    fn use_as(&mut self, local: &RcLocal, r#type: Type) {
+        self.uses.entry(local.clone()).or_default().push(r#type);
+    }
+
+    // combines the definitions and uses of the pass that just ran
+    fn resolve(&mut self) -> FxHashMap<RcLocal, Type> {
+        let mut uses = std::mem::take(&mut self.uses);
+        let mut types = FxHashMap::default();
+        for (local, definitions) in std::mem::take(&mut self.definitions) {
+            let defined = definitions.into_iter().reduce(Type::union).unwrap();
+            let used = uses
+                .remove(&local)
+                .and_then(|uses| uses.into_iter().reduce(Type::union));
+            let r#type = match (defined, used) {
+                // a value we know nothing about could be anything with fields, i.e. an instance,
+                // so it's only given a shape if it's assigned a table
+                (Type::Any, Some(used)) if !matches!(used, Type::Table { .. }) => used,
+                (defined @ Type::Table { .. }, Some(used @ Type::Table { .. })) => {
+                    defined.union(used)
+                }
+                (defined, _) => defined,
+            };
+            types.insert(local, r#type);
+        }
+        // locals that are never assigned to, i.e. parameters, could be given anything
+        for (local, uses) in uses {
+            let used = uses.into_iter().reduce(Type::union).unwrap();
+            if !matches!(used, Type::Table { .. }) {
+                types.insert(local, used);
+            }
+        }
+        types
+    }
+
+    fn annotation(&self, local: &RcLocal) -> Type {
+        let r#type = self.type_of(local);
+        if r#type.is_informative() {
+            r#type.clone()
+        } else {
+            Type::Any
+        }
+    }
+
+    fn annotate_block(&self, block: &mut Block) {
+        for statement in &mut block.0 {
+            if let Statement::Assign(assign) = statement
+                && assign.prefix
+            {
+                let types = assign
+                    .left
+                    .iter()
+                    .map(|lvalue| match lvalue {
+                        LValue::Local(local) => self.annotation(local),
+                        _ => Type::Any,
+                    })
+                    .collect_vec();
+                if types.iter().any(|r#type| *r#type != Type::Any) {
+                    assign.types = types;
+                }
+            }
+            statement.traverse_rvalues(&mut |rvalue| {
+                if let RValue::Closure(closure) = rvalue {
+                    self.annotate_function(&mut closure.function.lock());
+                }
+            });
+            match statement {
+                Statement::If(r#if) => {
+                    self.annotate_block(&mut r#if.then_block.lock());
+                    self.annotate_block(&mut r#if.else_block.lock());
+                }
+                Statement::While(r#while) => {
+                    self.annotate_block(&mut r#while.block.lock());
+                }
+                Statement::Do(r#do) => {
+                    self.annotate_block(&mut r#do.block.lock());
+                }
+                Statement::Repeat(repeat) => {
+                    self.annotate_block(&mut repeat.block.lock());
+                }
+                Statement::NumericFor(numeric_for) => {
+                    self.annotate_block(&mut numeric_for.block.lock());
+                }
+                Statement::GenericFor(generic_for) => {
+                    self.annotate_block(&mut generic_for.block.lock());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn annotate_function(&self, function: &mut Function) {
+        let parameter_types = function
+            .parameters
+            .iter()
+            .enumerate()
+            .map(|(i, parameter)| match function.parameter_types.get(i) {
+                // types from the bytecode's type info are kept
+                Some(r#type) if *r#type != Type::Any => r#type.clone(),
+                _ => self.annotation(parameter),
+            })
+            .collect_vec();
+        if parameter_types.iter().any(|r#type| *r#type != Type::Any) {
+            function.parameter_types = parameter_types;
+        }
+        function.return_types = self.return_types(&function.body);
+        self.annotate_block(&mut function.body);
+    }
+
+    // the types of the values returned from a function with `body`, empty if they aren't
+    // known or it doesn't always return the same number of values
+    pub(crate) fn return_types(&self, body: &Block) -> Vec<Type> {
+        if !matches!(body.last(), Some(Statement::Return(_))) {
+            return Vec::new();
+        }
+        let mut returns = Vec::new();
+        self.collect_returns(body, &mut returns);
+        let count = returns[0].len();
+        if returns.iter().any(|types| types.len() != count) {
+            return Vec::new();
+        }
+        let types = (0..count)
+            .map(|i| {
+                let r#type = returns
+                    .iter()
+                    .map(|types| types[i].clone())
+                    .reduce(Type::union)
+                    .unwrap();
+                if r#type.is_informative() {
+                    r#type
+                } else {
+                    Type::Any
+                }
+            })
+            .collect_vec();
+        if types.iter().any(|r#type| *r#type != Type::Any) {
+            types
+        } else {
+            Vec::new()
+        }
+    }
+
+    // the types of the values of every return in `block`, except in functions in it
+    fn collect_returns(&self, block: &Block, returns: &mut Vec<Vec<Type>>) {
+        for statement in block.iter() {
+            match statement {
+                Statement::Return(r#return) => {
+                    let count = r#return.values.len();
+                    returns.push(
+                        r#return
+                            .values
+                            .iter()
+                            .enumerate()
+                            .map(|(i, value)| match value {
+                                // could be any number of values
+                                RValue::Select(_) if i + 1 == count => Type::VarArg,
+                                _ => value.infer(self),
+                            })
+                            .collect(),
+                    );
+                }
+                Statement::If(r#if) => {
+                    self.collect_returns(&r#if.then_block.lock(), returns);
+                    self.collect_returns(&r#if.else_block.lock(), returns);
+                }
+                Statement::While(r#while) => {
+                    self.collect_returns(&r#while.block.lock(), returns);
+                }
+                Statement::Do(r#do) => {
+                    self.collect_returns(&r#do.block.lock(), returns);
+                }
+                Statement::Repeat(repeat) => {
+                    self.collect_returns(&repeat.block.lock(), returns);
+                }
+                Statement::NumericFor(numeric_for) => {
+                    self.collect_returns(&numeric_for.block.lock(), returns);
+                }
+                Statement::GenericFor(generic_for) => {
+                    self.collect_returns(&generic_for.block.lock(), returns);
+                }
+                _ => {}
+            }
+        }
     }
 
     pub fn type_of(&self, local: &RcLocal) -> &Type {
-        self.annotations
-            .get(local)
-            .map(|t| t as &Type)
-            .unwrap_or(&Type::Any)
+        self.types.get(local).unwrap_or(&Type::Any)
     }
 }
 
 pub trait Infer {
-    fn infer<'a: 'b, 'b>(&'a mut self, system: &mut TypeSystem<'b>) -> Type;
+    fn infer(&self, system: &TypeSystem) -> Type;
 }
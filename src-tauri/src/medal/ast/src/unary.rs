@@ -1,6 +1,9 @@
 use std::fmt;
 
-use crate::{Literal, LocalRw, RValue, RcLocal, Reduce, SideEffects, Traverse};
+use crate::{
+    type_system::{Infer, TypeSystem},
+    Literal, LocalRw, RValue, RcLocal, Reduce, SideEffects, Traverse, Type,
+};
 
 use super::{Binary, BinaryOperation};
 
@@ -47,6 +50,19 @@ impl Traverse for Unary {
     }
 }
 
+impl Infer for Unary {
+    fn infer(&self, system: &TypeSystem) -> Type {
+        match self.operation {
+            UnaryOperation::Not => Type::Boolean,
+            UnaryOperation::Negate => match self.value.infer(system) {
+                r#type @ (Type::Number | Type::Vector) => r#type,
+                _ => Type::Any,
+            },
+            UnaryOperation::Length => Type::Number,
+        }
+    }
+}
+
 impl Reduce for Unary {
     fn reduce(self) -> RValue {
         // TODO: unnecessary clone
@@ -14,6 +14,7 @@ pub struct Function {
     pub id: usize,
     pub name: Option<String>,
     pub parameters: Vec<RcLocal>,
+    pub parameter_types: Vec<ast::type_system::Type>,
     pub is_variadic: bool,
     graph: StableDiGraph<ast::Block, BlockEdge>,
     entry: Option<NodeIndex>,
@@ -25,6 +26,7 @@ impl Function {
             id,
             name: None,
             parameters: Vec::new(),
+            parameter_types: Vec::new(),
             is_variadic: false,
             graph: StableDiGraph::new(),
             entry: None,
@@ -17,17 +17,17 @@ use super::upvalues::UpvaluesOpen;
 struct SsaConstructor<'a> {
     function: &'a mut Function,
     dfs: IndexSet<NodeIndex>,
-    incomplete_params: FxHashMap<NodeIndex, FxHashMap<RcLocal, RcLocal>>,
+    incomplete_params: FxHashMap<NodeIndex, IndexMap<RcLocal, RcLocal>>,
     filled_blocks: FxHashSet<NodeIndex>,
     sealed_blocks: FxHashSet<NodeIndex>,
     // TODO: combine current/all/old into one map
     current_definition: FxHashMap<RcLocal, FxHashMap<NodeIndex, RcLocal>>,
-    all_definitions: FxHashMap<RcLocal, FxHashSet<RcLocal>>,
+    all_definitions: IndexMap<RcLocal, IndexSet<RcLocal>>,
     old_locals: FxHashMap<RcLocal, RcLocal>,
     local_count: usize,
-    local_map: FxHashMap<RcLocal, RcLocal>,
-    new_upvalues_in: IndexMap<RcLocal, FxHashSet<RcLocal>>,
-    upvalues_passed: FxHashMap<RcLocal, FxHashMap<(NodeIndex, usize), FxHashSet<RcLocal>>>,
+    local_map: IndexMap<RcLocal, RcLocal>,
+    new_upvalues_in: IndexMap<RcLocal, IndexSet<RcLocal>>,
+    upvalues_passed: IndexMap<RcLocal, IndexMap<(NodeIndex, usize), IndexSet<RcLocal>>>,
 }
 
 // TODO: REFACTOR: move out of construct module
@@ -35,12 +35,12 @@ struct SsaConstructor<'a> {
 // https://github.com/fkie-cad/dewolf/blob/7afe5b46e79a7b56e9904e63f29d54bd8f7302d9/decompiler/pipeline/ssa/phi_cleaner.py
 pub fn remove_unnecessary_params(
     function: &mut Function,
-    local_map: &mut FxHashMap<RcLocal, RcLocal>,
+    local_map: &mut IndexMap<RcLocal, RcLocal>,
 ) -> bool {
     let mut changed = false;
     for node in function.blocks().map(|(i, _)| i).collect::<Vec<_>>() {
         let mut dependency_graph = ParamDependencyGraph::new(function, node);
-        let mut removable_params = FxHashMap::default();
+        let mut removable_params = IndexMap::new();
         let edges = function
             .graph()
             .edges_directed(node, Direction::Incoming)
@@ -159,7 +159,7 @@ pub fn remove_unnecessary_params(
 // TODO: STYLE: rename `uses_local`, we need a generic name for ast nodes, maybe `traversible`?
 fn apply_local_map_to_values_referenced<T: LocalRw + Traverse>(
     uses_local: &mut T,
-    local_map: &FxHashMap<RcLocal, RcLocal>,
+    local_map: &IndexMap<RcLocal, RcLocal>,
 ) {
     // TODO: figure out values_mut
     for (from, mut to) in uses_local
@@ -191,8 +191,23 @@ fn apply_local_map_to_values_referenced<T: LocalRw + Traverse>(
     // });
 }
 
+// ssa versions keep the name of the local they are a version of
+fn new_version(local: &RcLocal) -> RcLocal {
+    RcLocal::new(local.0 .0.lock().clone())
+}
+
 // does not replace locals in child closures
-pub fn apply_local_map(function: &mut Function, local_map: FxHashMap<RcLocal, RcLocal>) {
+pub fn apply_local_map(function: &mut Function, local_map: IndexMap<RcLocal, RcLocal>) {
+    // make sure names (i.e. from debug info) survive the replacement
+    for (from, mut to) in &local_map {
+        while let Some(to_to) = local_map.get(to) {
+            to = to_to;
+        }
+        if from != to && to.0 .0.lock().0.is_none() {
+            let name = from.0 .0.lock().0.clone();
+            to.0 .0.lock().0 = name;
+        }
+    }
     for param in &mut function.parameters {
         if let Some(mut new_param) = local_map.get(param) {
             // TODO: make sure this doesnt cycle if theres a li -> li entry
@@ -364,7 +379,7 @@ impl<'a> SsaConstructor<'a> {
             // search globally
             if !self.sealed_blocks.contains(&node) {
                 // TODO: this code is repeated multiple times, create new_local function
-                let param_local = RcLocal::default();
+                let param_local = new_version(local);
                 self.old_locals.insert(param_local.clone(), local.clone());
                 if let Some(upvalues) = self.new_upvalues_in.get_mut(local) {
                     upvalues.insert(param_local.clone());
@@ -378,7 +393,7 @@ impl<'a> SsaConstructor<'a> {
             } else if let Ok(pred) = self.function.predecessor_blocks(node).exactly_one() {
                 self.find_local(pred, local)
             } else {
-                let param_local = RcLocal::default();
+                let param_local = new_version(local);
                 self.old_locals.insert(param_local.clone(), local.clone());
                 if let Some(upvalues) = self.new_upvalues_in.get_mut(local) {
                     upvalues.insert(param_local.clone());
@@ -502,9 +517,9 @@ impl<'a> SsaConstructor<'a> {
         mut self,
     ) -> (
         usize,
-        Vec<FxHashSet<RcLocal>>,
-        Vec<(RcLocal, FxHashSet<RcLocal>)>,
-        Vec<FxHashSet<RcLocal>>,
+        Vec<IndexSet<RcLocal>>,
+        Vec<(RcLocal, IndexSet<RcLocal>)>,
+        Vec<IndexSet<RcLocal>>,
     ) {
         let entry = self.function.entry().unwrap();
         let mut visited_nodes = Vec::with_capacity(self.function.graph().node_count());
@@ -524,7 +539,7 @@ impl<'a> SsaConstructor<'a> {
                     && let Some(local) = assign.left[0].as_local().cloned()
                     && assign.right[0].as_closure().is_some()
                 {
-                    let new_local = RcLocal::default();
+                    let new_local = new_version(&local);
                     self.old_locals.insert(new_local.clone(), local.clone());
                     if let Some(upvalues) = self.new_upvalues_in.get_mut(&local) {
                         upvalues.insert(new_local.clone());
@@ -550,7 +565,7 @@ impl<'a> SsaConstructor<'a> {
                     self.read(node, stat_index);
                     // write
                     for (local_index, local) in written.iter().enumerate() {
-                        let new_local = RcLocal::default();
+                        let new_local = new_version(local);
                         self.old_locals.insert(new_local.clone(), local.clone());
                         if let Some(upvalues) = self.new_upvalues_in.get_mut(local) {
                             upvalues.insert(new_local.clone());
@@ -607,7 +622,9 @@ impl<'a> SsaConstructor<'a> {
         // TODO: this is a bit meh, maybe we should have an argument rvalue
         if let Some(mut incomplete_params) = self.incomplete_params.remove(&entry) {
             for param in &mut self.function.parameters {
-                *param = incomplete_params.remove(param).unwrap_or_default();
+                *param = incomplete_params
+                    .remove(param)
+                    .unwrap_or_else(|| new_version(param));
             }
         }
         assert!(self.incomplete_params.is_empty());
@@ -641,9 +658,9 @@ pub fn construct(
     upvalues_in: &Vec<RcLocal>,
 ) -> (
     usize,
-    Vec<FxHashSet<RcLocal>>,
-    Vec<(RcLocal, FxHashSet<RcLocal>)>,
-    Vec<FxHashSet<RcLocal>>,
+    Vec<IndexSet<RcLocal>>,
+    Vec<(RcLocal, IndexSet<RcLocal>)>,
+    Vec<IndexSet<RcLocal>>,
 ) {
     // if entry has predecessors, this might risk it never being incomplete
     // resulting in broken params
@@ -654,7 +671,7 @@ pub fn construct(
         .is_none());
     let mut new_upvalues_in = IndexMap::with_capacity(upvalues_in.len());
     for upvalue in upvalues_in {
-        new_upvalues_in.insert(upvalue.clone(), FxHashSet::default());
+        new_upvalues_in.insert(upvalue.clone(), IndexSet::new());
     }
 
     let dfs = Dfs::new(function.graph(), function.entry().unwrap())
@@ -675,12 +692,12 @@ pub fn construct(
         filled_blocks: FxHashSet::with_capacity_and_hasher(node_count, Default::default()),
         sealed_blocks: FxHashSet::with_capacity_and_hasher(node_count, Default::default()),
         current_definition: FxHashMap::default(),
-        all_definitions: FxHashMap::default(),
+        all_definitions: IndexMap::new(),
         old_locals: FxHashMap::default(),
         local_count: 0,
-        local_map: FxHashMap::default(),
+        local_map: IndexMap::new(),
         new_upvalues_in,
-        upvalues_passed: FxHashMap::default(),
+        upvalues_passed: IndexMap::new(),
     }
     .construct()
 }
@@ -1,7 +1,7 @@
 use std::{cell::RefCell, collections::BTreeMap, rc::Rc};
 
 use ast::{LocalRw, RcLocal};
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
 use itertools::Itertools;
 use petgraph::{
     algo::dominators::simple_fast,
@@ -44,11 +44,11 @@ type CongruenceClass = BTreeMap<(usize, ParamOrStatIndex), RcLocal>;
 pub struct Destructor<'a> {
     function: &'a mut Function,
     upvalue_to_group: IndexMap<RcLocal, RcLocal>,
-    upvalues_in: FxHashSet<RcLocal>,
-    values: FxHashMap<RcLocal, Rc<RefCell<FxHashSet<RcLocal>>>>,
+    upvalues_in: IndexSet<RcLocal>,
+    values: FxHashMap<RcLocal, Rc<RefCell<IndexSet<RcLocal>>>>,
     // map( local -> rc_map( local -> (pre-order block index, param index) ) )
     // TODO: hash map?
-    congruence_classes: FxHashMap<RcLocal, Rc<RefCell<CongruenceClass>>>,
+    congruence_classes: IndexMap<RcLocal, Rc<RefCell<CongruenceClass>>>,
     equal_ancestor_in: FxHashMap<RcLocal, RcLocal>,
     equal_ancestor_out: FxHashMap<RcLocal, RcLocal>,
     local_defs: FxHashMap<RcLocal, (usize, NodeIndex, ParamOrStatIndex)>,
@@ -64,7 +64,7 @@ impl<'a> Destructor<'a> {
     pub fn new(
         function: &'a mut Function,
         upvalue_to_group: IndexMap<RcLocal, RcLocal>,
-        upvalues_in: FxHashSet<RcLocal>,
+        upvalues_in: IndexSet<RcLocal>,
         local_count: usize,
     ) -> Self {
         Self {
@@ -72,10 +72,7 @@ impl<'a> Destructor<'a> {
             upvalue_to_group,
             upvalues_in,
             values: FxHashMap::with_capacity_and_hasher(local_count, Default::default()),
-            congruence_classes: FxHashMap::with_capacity_and_hasher(
-                local_count,
-                Default::default(),
-            ),
+            congruence_classes: IndexMap::with_capacity(local_count),
             equal_ancestor_in: FxHashMap::default(),
             equal_ancestor_out: FxHashMap::default(),
             local_defs: FxHashMap::with_capacity_and_hasher(local_count, Default::default()),
@@ -238,8 +235,8 @@ impl<'a> Destructor<'a> {
         }
     }
 
-    fn build_local_map(&self) -> FxHashMap<RcLocal, RcLocal> {
-        let mut map = FxHashMap::default();
+    fn build_local_map(&self) -> IndexMap<RcLocal, RcLocal> {
+        let mut map = IndexMap::new();
         for (local, con_class) in &self.congruence_classes {
             let con_class = con_class.borrow();
             let new_local = con_class.iter().next().unwrap().1;
@@ -770,17 +767,40 @@ impl<'a> Destructor<'a> {
         }
     }
 
-    fn get_value_class(&mut self, local: RcLocal) -> &Rc<RefCell<FxHashSet<RcLocal>>> {
+    fn get_value_class(&mut self, local: RcLocal) -> &Rc<RefCell<IndexSet<RcLocal>>> {
         self.values.entry(local.clone()).or_insert_with(|| {
-            let mut value_class = FxHashSet::default();
+            let mut value_class = IndexSet::new();
             value_class.insert(local);
             Rc::new(RefCell::new(value_class))
         })
     }
 
+    // every edge to a block has to pass its params in the same order,
+    // the order of the first edge is used so that it doesn't depend on addresses
     fn sort_params(&mut self) {
-        for edge in self.function.graph_mut().edge_weights_mut() {
-            edge.arguments.sort_by(|(p0, _), (p1, _)| p0.cmp(p1));
+        for node in self.function.graph().node_indices().collect::<Vec<_>>() {
+            let Some((_, first_edge)) = self.function.edges_to_block(node).next() else {
+                continue;
+            };
+            let order = first_edge
+                .arguments
+                .iter()
+                .map(|(p, _)| p.clone())
+                .collect::<IndexSet<_>>();
+            for edge in self
+                .function
+                .graph()
+                .edges_directed(node, Direction::Incoming)
+                .map(|e| e.id())
+                .collect::<Vec<_>>()
+            {
+                self.function
+                    .graph_mut()
+                    .edge_weight_mut(edge)
+                    .unwrap()
+                    .arguments
+                    .sort_by_key(|(p, _)| order.get_index_of(p));
+            }
         }
     }
 
@@ -793,7 +813,7 @@ impl<'a> Destructor<'a> {
     // Note that the phi-functions do not have a circular dependency and are ordered accordingly (we have to do this before),
     // i.e., no variable that is defined by a Phi-function is used in a 'later' phi-function.
     fn lift_block_params(&mut self, node: NodeIndex) {
-        let mut param_map = FxHashMap::default();
+        let mut param_map = IndexMap::new();
         if let Some((_, BlockEdge { arguments, .. })) = self.function.edges_to_block(node).next() {
             for param in arguments.iter().map(|(p, _)| p) {
                 let temp_param = RcLocal::default();
@@ -813,6 +833,8 @@ impl<'a> Destructor<'a> {
                     right: param_map.values().map(|v| v.clone().into()).collect(),
                     prefix: false,
                     parallel: true,
+                    line: None,
+                    types: Vec::new(),
                 }
                 .into(),
             );
@@ -853,6 +875,8 @@ impl<'a> Destructor<'a> {
                     right: Vec::with_capacity(args.len()),
                     prefix: false,
                     parallel: true,
+                    line: None,
+                    types: Vec::new(),
                 };
 
                 for (param, arg) in args {
@@ -868,7 +892,7 @@ impl<'a> Destructor<'a> {
                     parallel_assign
                         .right
                         .push(std::mem::replace(arg, temp_local.into()));
-                    *param = param_map[param].clone();
+                    *param = param_map[&*param].clone();
                 }
 
                 if !parallel_assign.left.is_empty() {
@@ -128,7 +128,10 @@ impl<'a> Inliner<'a> {
                     stat.values_read()
                         .into_iter()
                         .filter(|&l| {
-                            self.local_usages[l] == 1 && !self.upvalue_to_group.contains_key(l)
+                            // named locals come from debug info and exist in the source
+                            self.local_usages[l] == 1
+                                && !self.upvalue_to_group.contains_key(l)
+                                && l.0 .0.lock().0.is_none()
                         })
                         .cloned()
                         .map(Some)
@@ -334,7 +337,9 @@ impl<'a> Inliner<'a> {
                         a.values_read()
                             .into_iter()
                             .filter(|&l| {
-                                self.local_usages[l] == 1 && !self.upvalue_to_group.contains_key(l)
+                                self.local_usages[l] == 1
+                                    && !self.upvalue_to_group.contains_key(l)
+                                    && l.0 .0.lock().0.is_none()
                             })
                             .cloned()
                             .map(Some)
@@ -366,6 +366,111 @@ fn is_truthy(rvalue: ast::RValue) -> Option<bool> {
     }
 }
 
+// a value assigned in both branches of an if and passed to the block they join at
+struct Diamond {
+    res_local: ast::RcLocal,
+    then_value: ast::RValue,
+    else_value: ast::RValue,
+    then_block: NodeIndex,
+    else_block: NodeIndex,
+}
+
+// the parameter a branch passes to the block after it and the value it's given, either
+// as the argument of an empty block or by the branch's only assignment
+fn branch_value(function: &Function, branch: NodeIndex) -> Option<(&ast::RcLocal, ast::RValue)> {
+    let next = function.unconditional_edge(branch)?;
+    let (param, arg) = next.weight().arguments.iter().exactly_one().ok()?;
+    let block = function.block(branch).unwrap();
+    if block.is_empty() {
+        Some((param, arg.clone()))
+    } else if let Some(assign) = single_assign(block)
+        // TODO: allow multiple unused (excl. first) locals in left
+        && assign.left.len() == 1 && assign.right.len() == 1
+        && let ast::RValue::Local(arg) = arg
+        && assign.left[0].as_local() == Some(arg)
+    {
+        // TODO: make sure arg isn't used outside the branch and the argument passed to next
+        Some((param, assign.right[0].clone()))
+    } else {
+        None
+    }
+}
+
+fn match_diamond(function: &Function, node: NodeIndex) -> Option<Diamond> {
+    let (then_edge, else_edge) = function.conditional_edges(node)?;
+    let (then_block, else_block) = (then_edge.target(), else_edge.target());
+    if then_edge.weight().arguments.is_empty()
+        && else_edge.weight().arguments.is_empty()
+        && then_block != else_block
+        && function
+            .predecessor_blocks(then_block)
+            .exactly_one()
+            .is_ok()
+        && function
+            .predecessor_blocks(else_block)
+            .exactly_one()
+            .is_ok()
+        && let Some(then_next) = function.unconditional_edge(then_block)
+        && let Some(else_next) = function.unconditional_edge(else_block)
+        && then_next.target() == else_next.target()
+        && let Some((then_param, then_value)) = branch_value(function, then_block)
+        && let Some((else_param, else_value)) = branch_value(function, else_block)
+        && then_param == else_param
+    {
+        Some(Diamond {
+            res_local: then_param.clone(),
+            then_value,
+            else_value,
+            then_block,
+            else_block,
+        })
+    } else {
+        None
+    }
+}
+
+// assigns `res` to the diamond's result in `node` and skips over both branches
+fn replace_diamond(function: &mut Function, node: NodeIndex, diamond: Diamond, res: ast::RValue) {
+    let Diamond {
+        res_local,
+        then_block,
+        else_block,
+        ..
+    } = diamond;
+    let (then_edge, else_edge) = (
+        function.unconditional_edge(then_block).unwrap().id(),
+        function.unconditional_edge(else_block).unwrap().id(),
+    );
+    function
+        .graph_mut()
+        .edge_weight_mut(then_edge)
+        .unwrap()
+        .arguments[0]
+        .1 = res_local.clone().into();
+    function
+        .graph_mut()
+        .edge_weight_mut(else_edge)
+        .unwrap()
+        .arguments[0]
+        .1 = res_local.clone().into();
+    skip_over_node(function, node, then_edge);
+    if function.predecessor_blocks(then_block).next().is_none() {
+        function.remove_block(then_block);
+    }
+    skip_over_node(function, node, else_edge);
+    if function.predecessor_blocks(else_block).next().is_none() {
+        function.remove_block(else_block);
+    }
+    let block = function.block_mut(node).unwrap();
+    let r#if = block.last_mut().unwrap().as_if_mut().unwrap();
+    r#if.condition = res_local.clone().into();
+    let pos = block.len() - 1;
+    block.insert(
+        pos,
+        ast::Assign::new(vec![res_local.into()], vec![res]).into(),
+    );
+}
+
 // TODO: STYLE: rename
 fn make_bool_conditional(
     function: &mut Function,
@@ -618,63 +723,12 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
             } else {
                 false
             }
-        } else if function.predecessor_blocks(then_edge.target()).exactly_one().is_ok()
-            && let Ok(then_next) = function.successor_blocks(then_edge.target()).exactly_one()
-            && function.predecessor_blocks(else_edge.target()).exactly_one().is_ok()
-            && let Ok(else_next) = function.successor_blocks(else_edge.target()).exactly_one()
-            && then_next == else_next
-            && let Some(then_assign) = single_assign(function.block(then_edge.target()).unwrap())
-            // TODO: allow multiple unused (excl. first) locals in left
-            && then_assign.left.len() == 1 && then_assign.right.len() == 1
-            && let Some(else_assign) = single_assign(function.block(else_edge.target()).unwrap())
-            // TODO: allow multiple unused (excl. first) locals in left
-            && else_assign.left.len() == 1 && else_assign.right.len() == 1
-            && let Ok((then_param, ast::RValue::Local(then_arg))) = then_edge.weight().arguments.iter().exactly_one()
-            && let Ok((else_param, ast::RValue::Local(else_arg))) = else_edge.weight().arguments.iter().exactly_one()
-            && then_param == else_param
-            && then_assign.left[0].as_local() == Some(then_arg)
-            && else_assign.left[0].as_local() == Some(else_arg)
-        {
-            // TODO: make sure then_arg and else_arg arent used outside their respective assigner blocks
-            // and the arguments passed to next
-            let res_local = then_param.clone();
-            let then_value = then_assign.right[0].clone();
-            let else_value = else_assign.right[0].clone();
-            let then_block = then_edge.target();
-            let else_block = else_edge.target();
-            let (then_edge, else_edge) = (
-                function.unconditional_edge(then_block).unwrap().id(),
-                function.unconditional_edge(else_block).unwrap().id(),
-            );
+        } else if let Some(diamond) = match_diamond(function, node) {
+            // TODO: unnecessary clones
+            let then_value = diamond.then_value.clone();
+            let else_value = diamond.else_value.clone();
             if let Some(res) = make_bool_conditional(function, node, then_value, else_value) {
-                function
-                    .graph_mut()
-                    .edge_weight_mut(then_edge)
-                    .unwrap()
-                    .arguments[0]
-                    .1 = res_local.clone().into();
-                function
-                    .graph_mut()
-                    .edge_weight_mut(else_edge)
-                    .unwrap()
-                    .arguments[0]
-                    .1 = res_local.clone().into();
-                skip_over_node(function, node, then_edge);
-                if function.predecessor_blocks(then_block).next().is_none() {
-                    function.remove_block(then_block);
-                }
-                skip_over_node(function, node, else_edge);
-                if function.predecessor_blocks(else_block).next().is_none() {
-                    function.remove_block(else_block);
-                }
-                let block = function.block_mut(node).unwrap();
-                let r#if = block.last_mut().unwrap().as_if_mut().unwrap();
-                r#if.condition = res_local.clone().into();
-                let pos = block.len() - 1;
-                block.insert(
-                    pos,
-                    ast::Assign::new(vec![res_local.into()], vec![res]).into(),
-                );
+                replace_diamond(function, node, diamond, res);
                 true
             } else {
                 false
@@ -692,10 +746,12 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
             && function.successor_blocks(else_target).next().is_none()
             && let Ok(ast::Statement::Return(ast::Return {
                 values: then_values,
+                ..
             })) = function.block(then_target).unwrap().iter().exactly_one()
             && let Ok(then_value) = then_values.iter().exactly_one()
             && let Ok(ast::Statement::Return(ast::Return {
                 values: else_values,
+                ..
             })) = function.block(else_target).unwrap().iter().exactly_one()
             && let Ok(else_value) = else_values.iter().exactly_one()
         {
@@ -722,6 +778,32 @@ fn structure_bool_conditional(function: &mut Function, node: NodeIndex) -> bool
     //todo!();
 }
 
+/// Turns `if c then a = x else a = y end` into `a = if c then x else y`.
+/// Only valid for Luau, and should run once `structure_conditionals` has nothing
+/// left to do so values that can be written with `and`/`or` still are
+pub fn structure_if_expressions(function: &mut Function) -> bool {
+    let mut did_structure = false;
+    let mut dfs = DfsPostOrder::new(function.graph(), function.entry().unwrap());
+    while let Some(node) = dfs.next(function.graph()) {
+        if let Some(ast::Statement::If(_)) = function.block(node).unwrap().last()
+            && let Some(diamond) = match_diamond(function, node)
+        {
+            let block = function.block_mut(node).unwrap();
+            let r#if = block.last_mut().unwrap().as_if_mut().unwrap();
+            let condition =
+                std::mem::replace(&mut r#if.condition, ast::Literal::Nil.into()).reduce_condition();
+            let res = ast::IfExpression::new(
+                condition,
+                diamond.then_value.clone(),
+                diamond.else_value.clone(),
+            );
+            replace_diamond(function, node, diamond, res.into());
+            did_structure = true;
+        }
+    }
+    did_structure
+}
+
 fn match_method_call(call: &ast::Call) -> Option<(&ast::RValue, &str)> {
     // TODO: make sure `a:method with space()` doesnt happen
     if !call.arguments.is_empty()
@@ -871,6 +953,8 @@ fn try_remove_unnecessary_condition(function: &mut Function, node: NodeIndex) ->
                     right: vec![cond],
                     prefix: true,
                     parallel: false,
+                    line: None,
+                    types: Vec::new(),
                 }
                 .into(),
             ),
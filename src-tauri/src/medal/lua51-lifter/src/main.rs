@@ -2,8 +2,8 @@
 #![feature(let_chains)]
 
 use ast::{
-    local_declarations::LocalDeclarer, name_locals::name_locals, replace_locals::replace_locals,
-    Traverse,
+    local_declarations::LocalDeclarer, name_gen::NumberedNameGenerator, name_locals::name_locals,
+    replace_locals::replace_locals, Traverse,
 };
 use by_address::ByAddress;
 use cfg::ssa::{
@@ -105,7 +105,7 @@ fn main() -> anyhow::Result<()> {
                 {
                     changed = true;
                 }
-                let mut local_map = FxHashMap::default();
+                let mut local_map = IndexMap::new();
                 // TODO: loop until returns false?
                 if ssa::construct::remove_unnecessary_params(&mut function, &mut local_map) {
                     changed = true;
@@ -143,7 +143,7 @@ fn main() -> anyhow::Result<()> {
     upvalues.remove(&main);
     let mut body = Arc::try_unwrap(main.0).unwrap().into_inner().body;
     link_upvalues(&mut body, &mut upvalues);
-    name_locals(&mut body, true);
+    name_locals(&mut body, true, &NumberedNameGenerator {});
     let res = body.to_string();
     let duration = start.elapsed();
 
@@ -190,6 +190,9 @@ fn link_upvalues(
             ast::Statement::While(r#while) => {
                 link_upvalues(&mut r#while.block.lock(), upvalues);
             }
+            ast::Statement::Do(r#do) => {
+                link_upvalues(&mut r#do.block.lock(), upvalues);
+            }
             ast::Statement::Repeat(repeat) => {
                 link_upvalues(&mut repeat.block.lock(), upvalues);
             }
@@ -1,6 +1,10 @@
-use nom::{bytes::complete::take, number::complete::le_u8, IResult};
+use nom::{bytes::complete::take, number::complete::le_u8};
 
-use super::chunk::Chunk;
+use super::{
+    chunk::Chunk,
+    error::{ParseError, ParseResult, Reason},
+    version::Capabilities,
+};
 
 #[derive(Debug)]
 pub enum Bytecode {
@@ -9,21 +13,26 @@ pub enum Bytecode {
 }
 
 impl Bytecode {
-    pub fn parse(input: &[u8], encode_key: u8) -> IResult<&[u8], Bytecode> {
-        let (input, status_code) = le_u8(input)?;
+    pub(crate) fn parse(input: &[u8], encode_key: u8) -> ParseResult<'_, Bytecode> {
+        let (remaining, status_code) = le_u8(input)?;
         match status_code {
             0 => {
-                let (input, error_msg) = take(input.len())(input)?;
+                let (input, error_msg) = take(remaining.len())(remaining)?;
                 Ok((
                     input,
                     Bytecode::Error(String::from_utf8_lossy(error_msg).to_string()),
                 ))
             }
-            4..=6 => {
-                let (input, chunk) = Chunk::parse(input, encode_key, status_code)?;
-                Ok((input, Bytecode::Chunk(chunk)))
-            }
-            _ => panic!("Unsupported bytecode version: {}", status_code),
+            version => match Capabilities::of(version) {
+                Some(capabilities) => {
+                    let (input, chunk) = Chunk::parse(remaining, encode_key, capabilities)?;
+                    Ok((input, Bytecode::Chunk(chunk)))
+                }
+                None => Err(nom::Err::Failure(ParseError::new(
+                    input,
+                    Reason::UnsupportedVersion(version),
+                ))),
+            },
         }
     }
 }
@@ -1,43 +1,239 @@
-use super::{function::Function, list::parse_list, parse_string};
-use nom::character::complete::char;
-use nom::multi::many_till;
+use super::{
+    error::{ParseError, ParseResult, Reason},
+    function::Function,
+    list::parse_list,
+    parse_string,
+    version::{Capabilities, LATEST_TYPES_VERSION},
+};
 use nom::number::complete::le_u8;
-use nom::IResult;
 use nom_leb128::leb128_usize;
 
 #[derive(Debug)]
 pub struct Chunk {
+    pub version: u8,
+    pub types_version: u8,
     pub string_table: Vec<Vec<u8>>,
+    // string table index of the name of each tagged userdata type,
+    // the type tag is LBC_TYPE_TAGGED_USERDATA_BASE + the index into this list
+    pub userdata_types: Vec<usize>,
     pub functions: Vec<Function>,
     pub main: usize,
 }
 
 impl Chunk {
-    pub(crate) fn parse(input: &[u8], encode_key: u8, version: u8) -> IResult<&[u8], Self> {
-        let (input, types_version) = if version >= 4 {
-            le_u8(input)?
+    pub(crate) fn parse(
+        input: &[u8],
+        encode_key: u8,
+        capabilities: Capabilities,
+    ) -> ParseResult<'_, Self> {
+        let (input, types_version) = if capabilities.has_type_info {
+            let (remaining, types_version) = le_u8(input)?;
+            if types_version > capabilities.max_types_version {
+                return Err(nom::Err::Failure(ParseError::new(
+                    input,
+                    Reason::UnsupportedTypesVersion(types_version),
+                )));
+            }
+            (remaining, types_version)
         } else {
             (input, 0)
         };
-        if types_version > 3 {
-            panic!("unsupported types version");
-        }
+        // type info layouts we don't know yet are read as the latest one
+        let layout_types_version = types_version.min(LATEST_TYPES_VERSION);
         let (input, string_table) = parse_list(input, parse_string)?;
-        let input = if types_version == 3 {
-            many_till(leb128_usize, char('\0'))(input)?.0
+        let mut userdata_types = Vec::new();
+        let input = if layout_types_version == 3 {
+            let (mut input, mut index) = le_u8(input)?;
+            while index != 0 {
+                let name;
+                (input, name) = leb128_usize(input)?;
+                let index_usize = index as usize;
+                if userdata_types.len() < index_usize {
+                    userdata_types.resize(index_usize, 0);
+                }
+                userdata_types[index_usize - 1] = name;
+                (input, index) = le_u8(input)?;
+            }
+            input
         } else {
             input
         };
-        let (input, functions) = parse_list(input, |i| Function::parse(i, encode_key))?;
+        let (mut input, function_count) = leb128_usize(input)?;
+        // the count comes from the input, but every function takes at least one byte of it
+        let mut functions = Vec::with_capacity(function_count.min(input.len()));
+        for function_index in 0..function_count {
+            let function;
+            (input, function) = Function::parse(
+                input,
+                encode_key,
+                capabilities,
+                layout_types_version,
+                string_table.len(),
+                function_index,
+            )
+            .map_err(|e| e.map(|e| e.in_function(function_index)))?;
+            functions.push(function);
+        }
+        let main_input = input;
         let (input, main) = leb128_usize(input)?;
+        if main >= functions.len() {
+            return Err(nom::Err::Failure(ParseError::new(
+                main_input,
+                Reason::InvalidFunctionIndex(main),
+            )));
+        }
 
         Ok((
             input,
             Self {
+                version: capabilities.version,
+                types_version,
                 string_table,
+                userdata_types,
                 functions,
                 main,
             },
         ))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        decompile_bytecode,
+        deserializer::{
+            bytecode::Bytecode,
+            deserialize,
+            error::{DeserializeError, Reason},
+        },
+        instruction::Instruction,
+    };
+
+    const LOP_GETGLOBAL: u32 = 7;
+    const LOP_LOADK: u32 = 5;
+    const LOP_RETURN: u32 = 22;
+    const RETURN_NOTHING: u32 = LOP_RETURN | 1 << 16;
+
+    // a function without type or debug info, the lists are already encoded
+    fn function(instructions: &[u32], constants: &[&[u8]], children: &[u8]) -> Vec<u8> {
+        let mut function = vec![1, 0, 0, 0, 0, 0, instructions.len() as u8];
+        for instruction in instructions {
+            function.extend(instruction.to_le_bytes());
+        }
+        function.push(constants.len() as u8);
+        for constant in constants {
+            function.extend(*constant);
+        }
+        function.push(children.len() as u8);
+        function.extend(children);
+        // line defined, no name, no line info and no debug info
+        function.extend([0, 0, 0, 0]);
+        function
+    }
+
+    // version 6 and types version 3 with one string, the last function is main
+    fn chunk(functions: &[Vec<u8>]) -> Vec<u8> {
+        chunk_with_version(6, functions)
+    }
+
+    fn chunk_with_version(version: u8, functions: &[Vec<u8>]) -> Vec<u8> {
+        let mut chunk = vec![version, 3, 1, 1, b'x', 0, functions.len() as u8];
+        for function in functions {
+            chunk.extend(function);
+        }
+        chunk.push(functions.len() as u8 - 1);
+        chunk
+    }
+
+    fn reason(bytecode: &[u8]) -> Reason {
+        match deserialize(bytecode, 1) {
+            Err(DeserializeError::Invalid { reason, .. }) => reason,
+            Err(err) => panic!("{}", err),
+            Ok(_) => panic!("bytecode was parsed"),
+        }
+    }
+
+    #[test]
+    fn function_count_past_the_input() {
+        // version 6, types version 3, no strings, no userdata types and then 2^63 - 1 functions
+        let mut bytecode = vec![6, 3, 0, 0];
+        bytecode.extend([0xff; 8]);
+        bytecode.push(0x7f);
+        assert!(deserialize(&bytecode, 1).is_err());
+    }
+
+    #[test]
+    fn main_past_the_functions() {
+        assert_eq!(reason(&[6, 3, 0, 0, 0, 5]), Reason::InvalidFunctionIndex(5));
+    }
+
+    #[test]
+    fn valid_chunk() {
+        let main = function(&[LOP_LOADK, RETURN_NOTHING], &[&[3, 1]], &[]);
+        assert!(deserialize(&chunk(&[main]), 1).is_ok());
+    }
+
+    #[test]
+    fn constant_past_the_constants() {
+        let main = function(&[LOP_LOADK | 1 << 16, RETURN_NOTHING], &[&[0]], &[]);
+        assert_eq!(reason(&chunk(&[main])), Reason::InvalidConstant(1));
+    }
+
+    #[test]
+    fn string_past_the_string_table() {
+        for index in [0, 2] {
+            let main = function(&[RETURN_NOTHING], &[&[3, index]], &[]);
+            assert_eq!(
+                reason(&chunk(&[main])),
+                Reason::InvalidStringIndex(index.into())
+            );
+        }
+    }
+
+    #[test]
+    fn global_name_not_a_string() {
+        let main = function(&[LOP_GETGLOBAL, 0, RETURN_NOTHING], &[&[0]], &[]);
+        assert_eq!(reason(&chunk(&[main])), Reason::InvalidConstant(0));
+    }
+
+    #[test]
+    fn function_refers_to_itself() {
+        // as a child and as a closure constant, either would make lifting loop forever
+        let child = function(&[RETURN_NOTHING], &[], &[0]);
+        assert_eq!(reason(&chunk(&[child])), Reason::InvalidFunctionIndex(0));
+        let closure = function(&[RETURN_NOTHING], &[&[6, 0]], &[]);
+        assert_eq!(reason(&chunk(&[closure])), Reason::InvalidFunctionIndex(0));
+    }
+
+    #[test]
+    fn table_keys() {
+        // a table keyed by the string constant "x" before it
+        let main = function(&[RETURN_NOTHING], &[&[3, 1], &[5, 1, 0]], &[]);
+        assert!(deserialize(&chunk(&[main]), 1).is_ok());
+        // keyed by itself, by a constant that doesn't exist and by nil
+        for key in [1, 2, 0] {
+            let main = function(&[RETURN_NOTHING], &[&[0], &[5, 1, key]], &[]);
+            assert_eq!(reason(&chunk(&[main])), Reason::InvalidConstant(1));
+        }
+    }
+
+    #[test]
+    fn unknown_op_codes() {
+        // op codes past the ones we know are only allowed from versions we don't know yet
+        let main = function(&[100, RETURN_NOTHING], &[], &[]);
+        let bytecode = chunk_with_version(7, std::slice::from_ref(&main));
+        let Ok(Bytecode::Chunk(parsed)) = deserialize(&bytecode, 1) else {
+            panic!("bytecode wasn't parsed");
+        };
+        assert_eq!(parsed.version, 7);
+        assert!(matches!(
+            parsed.functions[0].instructions[0],
+            Instruction::Unknown { op_code: 100, .. }
+        ));
+        assert_eq!(
+            decompile_bytecode(&bytecode, 1).unwrap(),
+            "-- unknown op 100"
+        );
+        assert_eq!(reason(&chunk(&[main])), Reason::InvalidOpCode(100));
+    }
+}
@@ -1,8 +1,8 @@
-use super::list::parse_list;
-use nom::{
-    number::complete::{le_f32, le_f64, le_u32, le_u8},
-    IResult,
+use super::{
+    error::{ParseError, ParseResult, Reason},
+    list::parse_list,
 };
+use nom::number::complete::{le_f32, le_f64, le_u32, le_u8};
 use nom_leb128::leb128_usize;
 
 const CONSTANT_NIL: u8 = 0;
@@ -28,7 +28,8 @@ pub enum Constant {
 }
 
 impl Constant {
-    pub(crate) fn parse(input: &[u8]) -> IResult<&[u8], Self> {
+    pub(crate) fn parse(input: &[u8]) -> ParseResult<'_, Self> {
+        let tag_input = input;
         let (input, tag) = le_u8(input)?;
         match tag {
             CONSTANT_NIL => Ok((input, Constant::Nil)),
@@ -63,7 +64,10 @@ impl Constant {
                 let (input, w) = le_f32(input)?;
                 Ok((input, Constant::Vector(x, y, z, w)))
             }
-            _ => panic!("{}", tag),
+            _ => Err(nom::Err::Failure(ParseError::new(
+                tag_input,
+                Reason::UnknownConstantTag(tag),
+            ))),
         }
     }
 }
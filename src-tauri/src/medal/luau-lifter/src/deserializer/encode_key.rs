@@ -0,0 +1,84 @@
+use super::{bytecode::Bytecode, chunk::Chunk, deserialize_with_key, error::DeserializeError};
+use crate::{instruction::Instruction, op_code::OpCode};
+
+// vanilla bytecode first, then the Roblox client key, then every other invertible key
+fn candidate_keys() -> impl Iterator<Item = u8> {
+    [1, 203]
+        .into_iter()
+        .chain((3..=255).step_by(2).filter(|&key| key != 203))
+}
+
+/// Deserializes `bytecode` with every candidate key and picks the one whose
+/// instructions make the most sense.
+/// Keys that fail to decode an instruction are rejected outright, the rest are
+/// scored on aux values, jump targets and unknown op codes.
+pub fn detect(bytecode: &[u8]) -> Result<(u8, Bytecode), DeserializeError> {
+    let mut best: Option<(usize, u8, Bytecode)> = None;
+    let mut first_error = None;
+    for key in candidate_keys() {
+        match deserialize_with_key(bytecode, key) {
+            // there is no code to decode, so any key will do
+            Ok(Bytecode::Error(msg)) => return Ok((key, Bytecode::Error(msg))),
+            Ok(Bytecode::Chunk(chunk)) => {
+                let problems = count_problems(&chunk);
+                if problems == 0 {
+                    return Ok((key, Bytecode::Chunk(chunk)));
+                }
+                if best.as_ref().is_none_or(|(best, ..)| problems < *best) {
+                    best = Some((problems, key, Bytecode::Chunk(chunk)));
+                }
+            }
+            Err(err) => {
+                first_error.get_or_insert(err);
+            }
+        }
+    }
+    match best {
+        Some((_, key, bytecode)) => Ok((key, bytecode)),
+        None => Err(first_error.unwrap()),
+    }
+}
+
+fn count_problems(chunk: &Chunk) -> usize {
+    let mut problems = 0;
+    for function in &chunk.functions {
+        let instructions = &function.instructions;
+        // the compiler always ends a function with a return
+        if instructions
+            .last()
+            .is_some_and(|i| i.op_code() != Some(OpCode::LOP_RETURN))
+        {
+            problems += 1;
+        }
+
+        // pcs that hold an aux value rather than an instruction
+        let mut is_aux = vec![false; instructions.len()];
+        for (pc, instruction) in instructions.iter().enumerate() {
+            if instruction.op_code().is_some_and(OpCode::has_aux) && pc + 1 < is_aux.len() {
+                is_aux[pc + 1] = true;
+            }
+        }
+
+        for (pc, instruction) in instructions.iter().enumerate() {
+            if is_aux[pc] {
+                continue;
+            }
+            // newer versions can have ops we don't know, but a wrong key makes a lot more of them
+            if matches!(instruction, Instruction::Unknown { .. }) {
+                problems += 1;
+            }
+            if let Some(offset) = instruction.jump_offset() {
+                let target = pc as isize + 1 + offset;
+                if target < 0 || target as usize >= instructions.len() || is_aux[target as usize] {
+                    problems += 1;
+                }
+            }
+            if let Some(constant) = instruction.constant_index() {
+                if constant >= function.constants.len() {
+                    problems += 1;
+                }
+            }
+        }
+    }
+    problems
+}
@@ -0,0 +1,136 @@
+use std::fmt;
+
+use nom::error::{ErrorKind, FromExternalError};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeserializeError {
+    /// The bytecode contains a compiler error message instead of a chunk
+    Compilation(String),
+    /// Parsing stopped at `offset`, `function` is the index of the function
+    /// being parsed at the time, if any
+    Invalid {
+        offset: usize,
+        function: Option<usize>,
+        reason: Reason,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Reason {
+    UnsupportedVersion(u8),
+    UnsupportedTypesVersion(u8),
+    UnknownConstantTag(u8),
+    /// The op code after applying the encode key
+    InvalidOpCode(u8),
+    /// An instruction that takes an aux value is the last word in the function
+    MissingAux,
+    UnexpectedEof,
+    Malformed(ErrorKind),
+    /// A string table index that's out of range, string table indices are 1-based
+    InvalidStringIndex(usize),
+    /// A function index that's out of range, functions can only refer to the ones before them
+    InvalidFunctionIndex(usize),
+    /// A constant index that's out of range, or refers to the wrong kind of constant
+    InvalidConstant(usize),
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::UnsupportedVersion(version) => {
+                write!(f, "unsupported bytecode version {}", version)
+            }
+            Reason::UnsupportedTypesVersion(version) => {
+                write!(f, "unsupported types version {}", version)
+            }
+            Reason::UnknownConstantTag(tag) => write!(f, "unknown constant tag {}", tag),
+            Reason::InvalidOpCode(op_code) => write!(f, "invalid op code {}", op_code),
+            Reason::MissingAux => write!(f, "instruction is missing its aux value"),
+            Reason::UnexpectedEof => write!(f, "unexpected end of input"),
+            Reason::Malformed(kind) => write!(f, "malformed input ({:?})", kind),
+            Reason::InvalidStringIndex(index) => write!(f, "invalid string index {}", index),
+            Reason::InvalidFunctionIndex(index) => write!(f, "invalid function index {}", index),
+            Reason::InvalidConstant(index) => write!(f, "invalid constant {}", index),
+        }
+    }
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeserializeError::Compilation(message) => {
+                write!(f, "bytecode contains a compilation error: {}", message)
+            }
+            DeserializeError::Invalid {
+                offset,
+                function,
+                reason,
+            } => {
+                write!(f, "failed to parse bytecode at offset {:#x}", offset)?;
+                if let Some(function) = function {
+                    write!(f, " in function {}", function)?;
+                }
+                write!(f, ": {}", reason)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+/// The nom error type used by the deserializer, converted into a
+/// [`DeserializeError`] once the offset into the whole input is known
+#[derive(Debug)]
+pub(crate) struct ParseError<'a> {
+    pub(crate) input: &'a [u8],
+    pub(crate) function: Option<usize>,
+    pub(crate) reason: Reason,
+}
+
+impl<'a> ParseError<'a> {
+    pub(crate) fn new(input: &'a [u8], reason: Reason) -> Self {
+        Self {
+            input,
+            function: None,
+            reason,
+        }
+    }
+
+    pub(crate) fn in_function(self, function: usize) -> Self {
+        Self {
+            function: self.function.or(Some(function)),
+            ..self
+        }
+    }
+
+    pub(crate) fn into_deserialize_error(self, bytecode: &[u8]) -> DeserializeError {
+        DeserializeError::Invalid {
+            offset: bytecode.len() - self.input.len(),
+            function: self.function,
+            reason: self.reason,
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<&'a [u8]> for ParseError<'a> {
+    fn from_error_kind(input: &'a [u8], kind: ErrorKind) -> Self {
+        let reason = if input.is_empty() || kind == ErrorKind::Eof {
+            Reason::UnexpectedEof
+        } else {
+            Reason::Malformed(kind)
+        };
+        Self::new(input, reason)
+    }
+
+    fn append(_: &'a [u8], _: ErrorKind, other: Self) -> Self {
+        other
+    }
+}
+
+impl<'a, E> FromExternalError<&'a [u8], E> for ParseError<'a> {
+    fn from_external_error(input: &'a [u8], kind: ErrorKind, _: E) -> Self {
+        nom::error::ParseError::from_error_kind(input, kind)
+    }
+}
+
+pub(crate) type ParseResult<'a, T> = nom::IResult<&'a [u8], T, ParseError<'a>>;
@@ -1,16 +1,24 @@
-use nom::{
-    number::complete::{le_u32, le_u8},
-    IResult,
-};
+use nom::number::complete::{le_u32, le_u8};
 use nom_leb128::leb128_usize;
 
 use super::{
     constant::Constant,
+    error::{ParseError, ParseResult, Reason},
     list::{parse_list, parse_list_len},
+    type_info::TypeInfo,
+    version::Capabilities,
 };
 
 use crate::{instruction::*, op_code::OpCode};
 
+/// Set on the main function of a module with a `--!native` hot comment
+pub const LPF_NATIVE_MODULE: u8 = 1 << 0;
+/// Set on functions that aren't profitable to compile natively
+pub const LPF_NATIVE_COLD: u8 = 1 << 1;
+/// Set on functions with the `@native` attribute, and on the main function of a
+/// module that has any of them
+pub const LPF_NATIVE_FUNCTION: u8 = 1 << 2;
+
 #[derive(Debug)]
 #[allow(dead_code)]
 pub struct Function {
@@ -18,6 +26,9 @@ pub struct Function {
     pub num_parameters: u8,
     pub num_upvalues: u8,
     pub is_vararg: bool,
+    /// LPF_* flags, always 0 before version 4
+    pub flags: u8,
+    pub parameter_types: Vec<u8>,
     pub instructions: Vec<Instruction>,
     pub constants: Vec<Constant>,
     pub functions: Vec<usize>,
@@ -26,47 +37,106 @@ pub struct Function {
     pub line_gap_log2: Option<u8>,
     pub line_info_delta: Option<Vec<u8>>,
     pub abs_line_info_delta: Option<Vec<u32>>,
+    pub local_variables: Vec<LocalVariable>,
+    pub upvalue_names: Vec<usize>,
+}
+
+#[derive(Debug)]
+pub struct LocalVariable {
+    /// 1-based index into the string table, 0 if the local has no name
+    pub name: usize,
+    pub start_pc: usize,
+    pub end_pc: usize,
+    pub register: u8,
+}
+
+impl LocalVariable {
+    fn parse(input: &[u8]) -> ParseResult<'_, Self> {
+        let (input, name) = leb128_usize(input)?;
+        let (input, start_pc) = leb128_usize(input)?;
+        let (input, end_pc) = leb128_usize(input)?;
+        let (input, register) = le_u8(input)?;
+        Ok((
+            input,
+            Self {
+                name,
+                start_pc,
+                end_pc,
+                register,
+            },
+        ))
+    }
 }
 
 impl Function {
-    fn parse_instructions(vec: &Vec<u32>, encode_key: u8) -> Vec<Instruction> {
+    /// The source line of every instruction, indexed by pc
+    pub fn instruction_lines(&self) -> Option<Vec<usize>> {
+        let line_gap_log2 = self.line_gap_log2?;
+        let line_info_delta = self.line_info_delta.as_ref()?;
+        let abs_line_info_delta = self.abs_line_info_delta.as_ref()?;
+
+        let abs_lines = abs_line_info_delta
+            .iter()
+            .scan(0u32, |line, &delta| {
+                *line = line.wrapping_add(delta);
+                Some(*line)
+            })
+            .collect::<Vec<_>>();
+
+        let mut offset = 0u8;
+        let lines = line_info_delta
+            .iter()
+            .enumerate()
+            .map(|(pc, &delta)| {
+                offset = offset.wrapping_add(delta);
+                let abs_line = abs_lines
+                    .get(pc >> line_gap_log2)
+                    .copied()
+                    .unwrap_or_default();
+                abs_line.wrapping_add(offset as u32) as usize
+            })
+            .collect();
+        Some(lines)
+    }
+
+    // `code` is the input starting at the first instruction, used for error offsets
+    fn parse_instructions<'a>(
+        code: &'a [u8],
+        vec: &[u32],
+        encode_key: u8,
+        capabilities: Capabilities,
+    ) -> Result<Vec<Instruction>, ParseError<'a>> {
         let mut v: Vec<Instruction> = Vec::new();
         let mut pc = 0;
 
-        loop {
-            let ins = Instruction::parse(vec[pc], encode_key).unwrap();
-            let op = match ins {
-                Instruction::BC { op_code, .. } => op_code,
-                Instruction::AD { op_code, .. } => op_code,
-                Instruction::E { op_code, .. } => op_code,
+        while pc < vec.len() {
+            let op_code = (vec[pc] as u8).wrapping_mul(encode_key);
+            let ins = Instruction::parse(vec[pc], encode_key).ok().filter(|ins| {
+                ins.op_code()
+                    .is_some_and(|op| capabilities.supports_op_code(op))
+            });
+            let ins = match ins {
+                Some(ins) => ins,
+                // we can't know whether an unknown op has an aux value,
+                // so the next word is always decoded as an instruction
+                None if capabilities.allows_unknown_op_codes => Instruction::Unknown {
+                    op_code,
+                    insn: vec[pc],
+                },
+                None => {
+                    return Err(ParseError::new(
+                        &code[pc * 4..],
+                        Reason::InvalidOpCode(op_code),
+                    ));
+                }
             };
 
             // handle ops with aux values
-            match op {
-                OpCode::LOP_GETGLOBAL
-                | OpCode::LOP_SETGLOBAL
-                | OpCode::LOP_GETIMPORT
-                | OpCode::LOP_GETTABLEKS
-                | OpCode::LOP_SETTABLEKS
-                | OpCode::LOP_NAMECALL
-                | OpCode::LOP_JUMPIFEQ
-                | OpCode::LOP_JUMPIFLE
-                | OpCode::LOP_JUMPIFLT
-                | OpCode::LOP_JUMPIFNOTEQ
-                | OpCode::LOP_JUMPIFNOTLE
-                | OpCode::LOP_JUMPIFNOTLT
-                | OpCode::LOP_NEWTABLE
-                | OpCode::LOP_SETLIST
-                | OpCode::LOP_FORGLOOP
-                | OpCode::LOP_LOADKX
-                | OpCode::LOP_FASTCALL2
-                | OpCode::LOP_FASTCALL2K
-                | OpCode::LOP_FASTCALL3
-                | OpCode::LOP_JUMPXEQKNIL
-                | OpCode::LOP_JUMPXEQKB
-                | OpCode::LOP_JUMPXEQKN
-                | OpCode::LOP_JUMPXEQKS => {
-                    let aux = vec[pc + 1];
+            match ins.op_code() {
+                Some(op) if op.has_aux() => {
+                    let aux = *vec
+                        .get(pc + 1)
+                        .ok_or_else(|| ParseError::new(&code[pc * 4..], Reason::MissingAux))?;
                     pc += 2;
                     match ins {
                         Instruction::BC {
@@ -98,29 +168,178 @@ impl Function {
                     pc += 1;
                 }
             }
+        }
+
+        Ok(v)
+    }
+
+    // an import is up to 3 indices of string constants, 10 bits each,
+    // with the number of indices in the top 2 bits
+    fn is_valid_import(constants: &[Constant], id: u32) -> bool {
+        let len = (id >> 30) as usize;
+        (1..=3).contains(&len)
+            && (0..len).all(|i| {
+                let index = (id >> (20 - i * 10)) & 1023;
+                matches!(constants.get(index as usize), Some(Constant::String(_)))
+            })
+    }
+
+    // the constant a string constant is expected at, e.g. the name of a global
+    fn check_string_constant(constants: &[Constant], index: usize) -> Result<(), Reason> {
+        match constants.get(index) {
+            Some(Constant::String(_)) => Ok(()),
+            _ => Err(Reason::InvalidConstant(index)),
+        }
+    }
+
+    fn check_constant_index(constants: &[Constant], index: usize) -> Result<(), Reason> {
+        if index < constants.len() {
+            Ok(())
+        } else {
+            Err(Reason::InvalidConstant(index))
+        }
+    }
 
-            if pc == vec.len() {
-                break;
+    // string indices are 1-based, and functions can only refer to the ones before them
+    fn check_constants(
+        constants: &[Constant],
+        string_count: usize,
+        function_index: usize,
+    ) -> Result<(), Reason> {
+        for (index, constant) in constants.iter().enumerate() {
+            match *constant {
+                Constant::String(string) if !(1..=string_count).contains(&string) => {
+                    return Err(Reason::InvalidStringIndex(string));
+                }
+                Constant::Import(id) if !Self::is_valid_import(constants, id as u32) => {
+                    return Err(Reason::InvalidConstant(index));
+                }
+                Constant::Closure(function) if function >= function_index => {
+                    return Err(Reason::InvalidFunctionIndex(function));
+                }
+                // tables are only ever keyed by string and number constants
+                Constant::Table(ref keys)
+                    if !keys.iter().all(|&key| {
+                        matches!(
+                            constants.get(key),
+                            Some(Constant::String(_) | Constant::Number(_))
+                        )
+                    }) =>
+                {
+                    return Err(Reason::InvalidConstant(index));
+                }
+                _ => {}
             }
         }
+        Ok(())
+    }
 
-        v
+    // the constants and child functions instructions refer to, returns the pc of the first
+    // instruction that refers to one that doesn't exist
+    fn check_instructions(
+        instructions: &[Instruction],
+        constants: &[Constant],
+        functions: &[usize],
+    ) -> Result<(), (usize, Reason)> {
+        for (pc, instruction) in instructions.iter().enumerate() {
+            let result = match *instruction {
+                Instruction::BC {
+                    op_code, b, c, aux, ..
+                } => match op_code {
+                    OpCode::LOP_GETGLOBAL | OpCode::LOP_SETGLOBAL | OpCode::LOP_NAMECALL => {
+                        Self::check_string_constant(constants, aux as usize)
+                    }
+                    OpCode::LOP_GETTABLEKS | OpCode::LOP_SETTABLEKS | OpCode::LOP_LOADKX => {
+                        Self::check_constant_index(constants, aux as usize)
+                    }
+                    OpCode::LOP_ADDK
+                    | OpCode::LOP_SUBK
+                    | OpCode::LOP_MULK
+                    | OpCode::LOP_DIVK
+                    | OpCode::LOP_MODK
+                    | OpCode::LOP_POWK
+                    | OpCode::LOP_IDIVK
+                    | OpCode::LOP_ANDK
+                    | OpCode::LOP_ORK => Self::check_constant_index(constants, c as usize),
+                    OpCode::LOP_SUBRK | OpCode::LOP_DIVRK => {
+                        Self::check_constant_index(constants, b as usize)
+                    }
+                    OpCode::LOP_FASTCALL2K => Self::check_constant_index(constants, aux as usize),
+                    _ => Ok(()),
+                },
+                Instruction::AD {
+                    op_code, d, aux, ..
+                } => {
+                    // negative indices wrap around to indices that are out of range
+                    let d = d as u16 as usize;
+                    match op_code {
+                        OpCode::LOP_LOADK | OpCode::LOP_DUPTABLE => {
+                            Self::check_constant_index(constants, d)
+                        }
+                        OpCode::LOP_GETIMPORT if !Self::is_valid_import(constants, aux) => {
+                            Err(Reason::InvalidConstant(d))
+                        }
+                        OpCode::LOP_JUMPXEQKN | OpCode::LOP_JUMPXEQKS => {
+                            Self::check_constant_index(constants, (aux & ((1 << 24) - 1)) as usize)
+                        }
+                        OpCode::LOP_DUPCLOSURE => match constants.get(d) {
+                            Some(Constant::Closure(_)) => Ok(()),
+                            _ => Err(Reason::InvalidConstant(d)),
+                        },
+                        OpCode::LOP_NEWCLOSURE if d >= functions.len() => {
+                            Err(Reason::InvalidFunctionIndex(d))
+                        }
+                        _ => Ok(()),
+                    }
+                }
+                _ => Ok(()),
+            };
+            result.map_err(|reason| (pc, reason))?;
+        }
+        Ok(())
     }
 
-    pub(crate) fn parse(input: &[u8], encode_key: u8) -> IResult<&[u8], Self> {
+    pub(crate) fn parse(
+        input: &[u8],
+        encode_key: u8,
+        capabilities: Capabilities,
+        types_version: u8,
+        string_count: usize,
+        function_index: usize,
+    ) -> ParseResult<'_, Self> {
         let (input, max_stack_size) = le_u8(input)?;
         let (input, num_parameters) = le_u8(input)?;
         let (input, num_upvalues) = le_u8(input)?;
         let (input, is_vararg) = le_u8(input)?;
 
-        let (input, _flags) = le_u8(input)?;
-        let (input, _) = parse_list(input, le_u8)?;
+        let (input, flags, type_info) = if capabilities.has_type_info {
+            let (input, flags) = le_u8(input)?;
+            let (input, type_info) = TypeInfo::parse(input, types_version)?;
+            (input, flags, type_info)
+        } else {
+            (input, 0, TypeInfo::default())
+        };
 
-        let (input, u32_instructions) = parse_list(input, le_u32)?;
+        let (code, code_size) = leb128_usize(input)?;
+        let (input, u32_instructions) = parse_list_len(code, le_u32, code_size)?;
         //let (input, instructions) = parse_list(input, Function::parse_instrution)?;
-        let instructions = Self::parse_instructions(&u32_instructions, encode_key);
+        let instructions =
+            Self::parse_instructions(code, &u32_instructions, encode_key, capabilities)
+                .map_err(nom::Err::Failure)?;
+        let constants_input = input;
         let (input, constants) = parse_list(input, Constant::parse)?;
+        Self::check_constants(&constants, string_count, function_index)
+            .map_err(|reason| nom::Err::Failure(ParseError::new(constants_input, reason)))?;
+        let functions_input = input;
         let (input, functions) = parse_list(input, leb128_usize)?;
+        if let Some(&function) = functions.iter().find(|&&f| f >= function_index) {
+            return Err(nom::Err::Failure(ParseError::new(
+                functions_input,
+                Reason::InvalidFunctionIndex(function),
+            )));
+        }
+        Self::check_instructions(&instructions, &constants, &functions)
+            .map_err(|(pc, reason)| nom::Err::Failure(ParseError::new(&code[pc * 4..], reason)))?;
         let (input, line_defined) = leb128_usize(input)?;
         let (input, function_name) = leb128_usize(input)?;
         let (input, has_line_info) = le_u8(input)?;
@@ -145,27 +364,21 @@ impl Function {
                 let (input, abs_line_info_delta) = parse_list_len(
                     input,
                     le_u32,
-                    ((u32_instructions.len() - 1) >> line_gap_log2.unwrap()) + 1,
+                    (u32_instructions.len().saturating_sub(1))
+                        .checked_shr(line_gap_log2.unwrap().into())
+                        .unwrap_or(0)
+                        + 1,
                 )?;
                 (input, Some(abs_line_info_delta))
             }
         };
-        let input = match le_u8(input)? {
-            (input, 0) => input,
-            (_input, _) => {
-                panic!("we have debug info");
-                // let (mut input, num_locvars) = leb128_usize(input)?;
-                // for _ in 0..num_locvars {
-                //     (input, _) = leb128_usize(input)?;
-                //     (input, _) = leb128_usize(input)?;
-                //     (input, _) = leb128_usize(input)?;
-                //     (input, _) = le_u8(input)?;
-                // }
-                // let (mut input, num_upvalues) = leb128_usize(input)?;
-                // for _ in 0..num_upvalues {
-                //     (input, _) = leb128_usize(input)?;
-                // }
-                // input
+        let (input, has_debug_info) = le_u8(input)?;
+        let (input, local_variables, upvalue_names) = match has_debug_info {
+            0 => (input, Vec::new(), Vec::new()),
+            _ => {
+                let (input, local_variables) = parse_list(input, LocalVariable::parse)?;
+                let (input, upvalue_names) = parse_list(input, leb128_usize)?;
+                (input, local_variables, upvalue_names)
             }
         };
         Ok((
@@ -175,6 +388,8 @@ impl Function {
                 num_parameters,
                 num_upvalues,
                 is_vararg: is_vararg != 0u8,
+                flags,
+                parameter_types: type_info.parameters,
                 instructions,
                 constants,
                 functions,
@@ -183,6 +398,8 @@ impl Function {
                 line_gap_log2,
                 line_info_delta,
                 abs_line_info_delta,
+                local_variables,
+                upvalue_names,
             },
         ))
     }
@@ -1,10 +1,12 @@
-use nom::{multi::count, IResult};
+use nom::multi::count;
 use nom_leb128::leb128_usize;
 
+use super::error::ParseResult;
+
 pub(crate) fn parse_list<'a, T>(
     input: &'a [u8],
-    parser: impl Fn(&'a [u8]) -> IResult<&'a [u8], T>,
-) -> IResult<&'a [u8], Vec<T>> {
+    parser: impl Fn(&'a [u8]) -> ParseResult<'a, T>,
+) -> ParseResult<'a, Vec<T>> {
     let (input, length) = leb128_usize(input)?;
     let (input, items) = count(parser, length)(input)?;
     Ok((input, items))
@@ -12,9 +14,9 @@ pub(crate) fn parse_list<'a, T>(
 
 pub(crate) fn parse_list_len<'a, T>(
     input: &'a [u8],
-    parser: impl Fn(&'a [u8]) -> IResult<&'a [u8], T>,
+    parser: impl Fn(&'a [u8]) -> ParseResult<'a, T>,
     length: usize,
-) -> IResult<&'a [u8], Vec<T>> {
+) -> ParseResult<'a, Vec<T>> {
     let (input, items) = count(parser, length)(input)?;
     Ok((input, items))
 }
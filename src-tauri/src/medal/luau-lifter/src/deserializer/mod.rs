@@ -1,22 +1,52 @@
-use nom::{bytes::complete::take, IResult};
+use nom::bytes::complete::take;
 use nom_leb128::leb128_usize;
 
 pub mod bytecode;
 pub mod chunk;
 pub mod constant;
+pub mod encode_key;
+pub mod error;
 pub mod function;
 mod list;
+pub mod type_info;
+pub mod version;
 
-fn parse_string(input: &[u8]) -> IResult<&[u8], Vec<u8>> {
+use error::{DeserializeError, ParseResult};
+
+fn parse_string(input: &[u8]) -> ParseResult<'_, Vec<u8>> {
     let (input, length) = leb128_usize(input)?;
     let (input, bytes) = take(length)(input)?;
     Ok((input, bytes.to_owned()))
 }
 
-pub fn deserialize(bytecode: &[u8], encode_key: u8) -> Result<bytecode::Bytecode, String> {
+/// An encode key of 0 is never valid, so it's used to request detecting the key
+pub const DETECT_ENCODE_KEY: u8 = 0;
+
+pub fn deserialize(
+    bytecode: &[u8],
+    encode_key: u8,
+) -> Result<bytecode::Bytecode, DeserializeError> {
+    if encode_key == DETECT_ENCODE_KEY {
+        encode_key::detect(bytecode).map(|(_, bytecode)| bytecode)
+    } else {
+        deserialize_with_key(bytecode, encode_key)
+    }
+}
+
+fn deserialize_with_key(
+    bytecode: &[u8],
+    encode_key: u8,
+) -> Result<bytecode::Bytecode, DeserializeError> {
     match bytecode::Bytecode::parse(bytecode, encode_key) {
         Ok((_, deserialized_bytecode)) => Ok(deserialized_bytecode),
-        Err(err) => Err(err.to_string()),
+        Err(nom::Err::Error(err) | nom::Err::Failure(err)) => {
+            Err(err.into_deserialize_error(bytecode))
+        }
+        Err(nom::Err::Incomplete(_)) => Err(DeserializeError::Invalid {
+            offset: bytecode.len(),
+            function: None,
+            reason: error::Reason::UnexpectedEof,
+        }),
     }
 }
 
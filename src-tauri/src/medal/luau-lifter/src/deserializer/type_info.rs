@@ -0,0 +1,62 @@
+use nom::{bytes::complete::take, number::complete::le_u8};
+use nom_leb128::leb128_usize;
+
+use super::error::ParseResult;
+
+pub const LBC_TYPE_NIL: u8 = 0;
+pub const LBC_TYPE_BOOLEAN: u8 = 1;
+pub const LBC_TYPE_NUMBER: u8 = 2;
+pub const LBC_TYPE_STRING: u8 = 3;
+pub const LBC_TYPE_TABLE: u8 = 4;
+pub const LBC_TYPE_FUNCTION: u8 = 5;
+pub const LBC_TYPE_THREAD: u8 = 6;
+pub const LBC_TYPE_USERDATA: u8 = 7;
+pub const LBC_TYPE_VECTOR: u8 = 8;
+pub const LBC_TYPE_BUFFER: u8 = 9;
+pub const LBC_TYPE_ANY: u8 = 15;
+pub const LBC_TYPE_TAGGED_USERDATA_BASE: u8 = 64;
+pub const LBC_TYPE_TAGGED_USERDATA_END: u8 = 64 + 32;
+pub const LBC_TYPE_OPTIONAL_BIT: u8 = 1 << 7;
+
+#[derive(Debug, Default)]
+pub struct TypeInfo {
+    pub parameters: Vec<u8>,
+}
+
+impl TypeInfo {
+    // the function signature is encoded as [LBC_TYPE_FUNCTION, num_parameters, parameters...]
+    fn parse_signature(signature: &[u8]) -> ParseResult<'_, Vec<u8>> {
+        if signature.is_empty() {
+            return Ok((signature, Vec::new()));
+        }
+        let (input, _) = le_u8(signature)?;
+        let (input, num_parameters) = le_u8(input)?;
+        let (input, parameters) = take(num_parameters)(input)?;
+        Ok((input, parameters.to_vec()))
+    }
+
+    pub(crate) fn parse(input: &[u8], types_version: u8) -> ParseResult<'_, Self> {
+        let (input, size) = leb128_usize(input)?;
+        let (input, blob) = take(size)(input)?;
+        if size == 0 {
+            return Ok((input, Self::default()));
+        }
+
+        let type_info = match types_version {
+            1 => Self {
+                parameters: Self::parse_signature(blob)?.1,
+            },
+            _ => {
+                // upvalue and local types follow the signature, but they aren't used
+                let (blob, signature_size) = leb128_usize(blob)?;
+                let (blob, _num_upvalues) = leb128_usize(blob)?;
+                let (blob, _num_locals) = leb128_usize(blob)?;
+                let (_, signature) = take(signature_size)(blob)?;
+                Self {
+                    parameters: Self::parse_signature(signature)?.1,
+                }
+            }
+        };
+        Ok((input, type_info))
+    }
+}
@@ -0,0 +1,62 @@
+use crate::op_code::OpCode;
+
+/// The oldest bytecode version that can be deserialized
+pub const MIN_VERSION: u8 = 3;
+/// The newest bytecode version whose layout and op codes are known
+pub const LATEST_VERSION: u8 = 6;
+/// The newest types version whose type info layout is known
+pub const LATEST_TYPES_VERSION: u8 = 3;
+
+/// What the deserializer can expect from a given bytecode version
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub version: u8,
+    /// The chunk starts with a types version and every function has a flags
+    /// byte followed by its type info
+    pub has_type_info: bool,
+    /// The newest types version the chunk may declare
+    pub max_types_version: u8,
+    /// The highest op code the compiler emits for this version
+    pub max_op_code: OpCode,
+    /// Op codes that aren't known are kept as [`Instruction::Unknown`] rather
+    /// than rejecting the bytecode
+    ///
+    /// [`Instruction::Unknown`]: crate::instruction::Instruction::Unknown
+    pub allows_unknown_op_codes: bool,
+}
+
+impl Capabilities {
+    pub fn of(version: u8) -> Option<Self> {
+        let capabilities = match version {
+            ..MIN_VERSION => return None,
+            3 => Self {
+                version,
+                has_type_info: false,
+                max_types_version: 0,
+                max_op_code: OpCode::LOP_JUMPXEQKS,
+                allows_unknown_op_codes: false,
+            },
+            4..=LATEST_VERSION => Self {
+                version,
+                has_type_info: true,
+                max_types_version: LATEST_TYPES_VERSION,
+                max_op_code: OpCode::LOP_IDIVK,
+                allows_unknown_op_codes: false,
+            },
+            // newer versions are assumed to keep the latest layout, anything
+            // that was added since is lifted as an opaque instruction
+            _ => Self {
+                version,
+                has_type_info: true,
+                max_types_version: u8::MAX,
+                max_op_code: OpCode::LOP_IDIVK,
+                allows_unknown_op_codes: true,
+            },
+        };
+        Some(capabilities)
+    }
+
+    pub fn supports_op_code(&self, op_code: OpCode) -> bool {
+        op_code as u8 <= self.max_op_code as u8
+    }
+}
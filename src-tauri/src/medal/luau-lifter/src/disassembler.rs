@@ -0,0 +1,273 @@
+use std::fmt::Write;
+
+use crate::{
+    builtin::builtin_name,
+    deserializer::{
+        chunk::Chunk,
+        constant::Constant,
+        function::{Function, LPF_NATIVE_COLD, LPF_NATIVE_FUNCTION, LPF_NATIVE_MODULE},
+    },
+    instruction::Instruction,
+    op_code::OpCode,
+};
+
+pub struct Disassembler<'a> {
+    chunk: &'a Chunk,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(chunk: &'a Chunk) -> Self {
+        Self { chunk }
+    }
+
+    pub fn disassemble(&self) -> String {
+        let mut output = format!(
+            "version {}, types version {}\n",
+            self.chunk.version, self.chunk.types_version
+        );
+        for function_id in 0..self.chunk.functions.len() {
+            output.push('\n');
+            self.disassemble_function(function_id, &mut output);
+        }
+        output
+    }
+
+    pub fn disassemble_function(&self, function_id: usize, output: &mut String) {
+        let function = &self.chunk.functions[function_id];
+
+        write!(output, "function {}", function_id).unwrap();
+        if let Some(name) = self.string(function.function_name) {
+            write!(output, " {}", name).unwrap();
+        } else if function_id == self.chunk.main {
+            write!(output, " (main)").unwrap();
+        }
+        writeln!(
+            output,
+            " (line {}): {} params{}, {} upvalues, max stack {}",
+            function.line_defined,
+            function.num_parameters,
+            if function.is_vararg { " + vararg" } else { "" },
+            function.num_upvalues,
+            function.max_stack_size,
+        )
+        .unwrap();
+        let flags = [
+            (LPF_NATIVE_MODULE, "native module"),
+            (LPF_NATIVE_COLD, "cold"),
+            (LPF_NATIVE_FUNCTION, "native"),
+        ]
+        .into_iter()
+        .filter(|&(flag, _)| function.flags & flag != 0)
+        .map(|(_, name)| name)
+        .collect::<Vec<_>>();
+        if !flags.is_empty() {
+            writeln!(output, "flags: {}", flags.join(", ")).unwrap();
+        }
+
+        if !function.constants.is_empty() {
+            writeln!(output, "constants:").unwrap();
+            for (index, constant) in function.constants.iter().enumerate() {
+                writeln!(
+                    output,
+                    "\tK{} = {}",
+                    index,
+                    self.constant(function, constant)
+                )
+                .unwrap();
+            }
+        }
+
+        if !function.functions.is_empty() {
+            writeln!(output, "children:").unwrap();
+            for (index, &child) in function.functions.iter().enumerate() {
+                write!(output, "\t{}: function {}", index, child).unwrap();
+                if let Some(name) = self
+                    .chunk
+                    .functions
+                    .get(child)
+                    .and_then(|f| self.string(f.function_name))
+                {
+                    write!(output, " {}", name).unwrap();
+                }
+                output.push('\n');
+            }
+        }
+
+        writeln!(output, "instructions:").unwrap();
+        for line in self.instructions(function_id) {
+            writeln!(output, "\t{}", line).unwrap();
+        }
+    }
+
+    /// One line per instruction, aux words are shown on the instruction that owns them
+    pub fn instructions(&self, function_id: usize) -> Vec<String> {
+        let function = &self.chunk.functions[function_id];
+        let lines = function.instruction_lines();
+        let mut output = Vec::with_capacity(function.instructions.len());
+        let mut pc = 0;
+        while let Some(&instruction) = function.instructions.get(pc) {
+            let op_code = instruction.op_code();
+            let mut line = format!("[{:>4}] ", pc);
+            if let Some(source_line) = lines.as_ref().and_then(|l| l.get(pc)) {
+                write!(line, "{:>4}: ", source_line).unwrap();
+            }
+            let mnemonic = match op_code {
+                Some(op_code) => format!("{:?}", op_code),
+                None => "UNKNOWN".to_string(),
+            };
+            write!(line, "{:<16}", mnemonic.trim_start_matches("LOP_")).unwrap();
+            let has_aux = op_code.is_some_and(OpCode::has_aux);
+            match instruction {
+                Instruction::BC { a, b, c, aux, .. } => {
+                    write!(line, " {} {} {}", a, b, c).unwrap();
+                    if has_aux {
+                        write!(line, " [{:#010x}]", aux).unwrap();
+                    }
+                }
+                Instruction::AD { a, d, aux, .. } => {
+                    write!(line, " {} {}", a, d).unwrap();
+                    if has_aux {
+                        write!(line, " [{:#010x}]", aux).unwrap();
+                    }
+                }
+                Instruction::E { e, .. } => {
+                    write!(line, " {}", e).unwrap();
+                }
+                Instruction::Unknown { op_code, insn } => {
+                    write!(line, " op {} [{:#010x}]", op_code, insn).unwrap();
+                }
+            }
+            let annotations = instruction
+                .jump_offset()
+                .map(|offset| format!("to [{}]", pc as isize + 1 + offset))
+                .into_iter()
+                .chain(self.preview(function, instruction))
+                .collect::<Vec<_>>();
+            if !annotations.is_empty() {
+                write!(line, " ; {}", annotations.join(", ")).unwrap();
+            }
+            output.push(line);
+
+            // skip the nop that takes the place of the aux word
+            pc += if has_aux { 2 } else { 1 };
+        }
+        output
+    }
+
+    fn preview(&self, function: &Function, instruction: Instruction) -> Option<String> {
+        let constant_index = match instruction {
+            Instruction::AD {
+                op_code: OpCode::LOP_NEWCLOSURE,
+                d,
+                ..
+            } => {
+                return function
+                    .functions
+                    .get(d as usize)
+                    .map(|child| format!("function {}", child));
+            }
+            Instruction::AD {
+                op_code: OpCode::LOP_JUMPXEQKB,
+                aux,
+                ..
+            } => {
+                return Some(format!(
+                    "{}{}",
+                    if aux >> 31 != 0 { "not " } else { "" },
+                    aux & 1 != 0
+                ));
+            }
+            Instruction::BC {
+                op_code:
+                    OpCode::LOP_FASTCALL
+                    | OpCode::LOP_FASTCALL1
+                    | OpCode::LOP_FASTCALL2
+                    | OpCode::LOP_FASTCALL2K
+                    | OpCode::LOP_FASTCALL3,
+                a,
+                ..
+            } => {
+                // FASTCALL2K also has a constant argument
+                return builtin_name(a)
+                    .map(str::to_string)
+                    .into_iter()
+                    .chain(
+                        instruction
+                            .constant_index()
+                            .and_then(|index| self.constant_preview(function, index)),
+                    )
+                    .reduce(|name, constant| format!("{}, {}", name, constant));
+            }
+            Instruction::BC {
+                op_code: OpCode::LOP_GETUPVAL | OpCode::LOP_SETUPVAL,
+                b,
+                ..
+            } => {
+                return function
+                    .upvalue_names
+                    .get(b as usize)
+                    .and_then(|&name| self.string(name));
+            }
+            _ => instruction.constant_index()?,
+        };
+        self.constant_preview(function, constant_index)
+    }
+
+    fn constant_preview(&self, function: &Function, constant_index: usize) -> Option<String> {
+        function.constants.get(constant_index).map(|constant| {
+            format!(
+                "K{} = {}",
+                constant_index,
+                self.constant(function, constant)
+            )
+        })
+    }
+
+    fn constant(&self, function: &Function, constant: &Constant) -> String {
+        match constant {
+            Constant::Nil => "nil".to_string(),
+            Constant::Boolean(value) => value.to_string(),
+            Constant::Number(value) => value.to_string(),
+            Constant::String(index) => self.quoted_string(*index),
+            Constant::Import(id) => {
+                let path = (0..(id >> 30) & 3)
+                    .map(|i| (id >> (20 - i * 10)) & 1023)
+                    .map(|k| match function.constants.get(k) {
+                        Some(Constant::String(index)) => {
+                            self.string(*index).unwrap_or_else(|| format!("<K{}>", k))
+                        }
+                        _ => format!("<K{}>", k),
+                    })
+                    .collect::<Vec<_>>();
+                format!("import {}", path.join("."))
+            }
+            Constant::Table(keys) => {
+                let keys = keys
+                    .iter()
+                    .map(|&k| match function.constants.get(k) {
+                        Some(Constant::String(index)) => self.quoted_string(*index),
+                        _ => format!("K{}", k),
+                    })
+                    .collect::<Vec<_>>();
+                format!("table {{{}}}", keys.join(", "))
+            }
+            Constant::Closure(function_id) => format!("closure function {}", function_id),
+            Constant::Vector(x, y, z, w) => format!("vector({}, {}, {}, {})", x, y, z, w),
+        }
+    }
+
+    // 1-based index into the string table, 0 means no string
+    fn string(&self, index: usize) -> Option<String> {
+        index
+            .checked_sub(1)
+            .and_then(|i| self.chunk.string_table.get(i))
+            .map(|s| String::from_utf8_lossy(s).into_owned())
+    }
+
+    fn quoted_string(&self, index: usize) -> String {
+        match self.string(index) {
+            Some(string) => format!("{:?}", string),
+            None => format!("<string {}>", index),
+        }
+    }
+}
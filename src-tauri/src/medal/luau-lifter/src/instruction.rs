@@ -110,6 +110,11 @@ pub enum Instruction {
         op_code: OpCode,
         e: i32,
     },
+    /// An op code from a newer bytecode version than we know about
+    Unknown {
+        op_code: u8,
+        insn: u32,
+    },
 }
 
 impl Instruction {
@@ -170,7 +175,126 @@ impl Instruction {
                 c: 0,
                 aux: 0,
             }),
-            _ => unreachable!("{}", op_code),
+            _ => Err(nom::error::ErrorKind::Switch),
+        }
+    }
+
+    pub fn op_code(&self) -> Option<OpCode> {
+        match *self {
+            Self::BC { op_code, .. } | Self::AD { op_code, .. } | Self::E { op_code, .. } => {
+                Some(op_code)
+            }
+            Self::Unknown { .. } => None,
+        }
+    }
+
+    /// The offset from the next instruction that this instruction may jump to
+    pub fn jump_offset(&self) -> Option<isize> {
+        match *self {
+            Self::AD {
+                op_code:
+                    OpCode::LOP_JUMP
+                    | OpCode::LOP_JUMPBACK
+                    | OpCode::LOP_JUMPIF
+                    | OpCode::LOP_JUMPIFNOT
+                    | OpCode::LOP_JUMPIFEQ
+                    | OpCode::LOP_JUMPIFLE
+                    | OpCode::LOP_JUMPIFLT
+                    | OpCode::LOP_JUMPIFNOTEQ
+                    | OpCode::LOP_JUMPIFNOTLE
+                    | OpCode::LOP_JUMPIFNOTLT
+                    | OpCode::LOP_JUMPXEQKNIL
+                    | OpCode::LOP_JUMPXEQKB
+                    | OpCode::LOP_JUMPXEQKN
+                    | OpCode::LOP_JUMPXEQKS
+                    | OpCode::LOP_FORNPREP
+                    | OpCode::LOP_FORNLOOP
+                    | OpCode::LOP_FORGPREP
+                    | OpCode::LOP_FORGLOOP
+                    | OpCode::LOP_FORGPREP_INEXT
+                    | OpCode::LOP_FORGPREP_NEXT,
+                d,
+                ..
+            } => Some(d as isize),
+            // LOADB jumps if C is not zero
+            Self::BC {
+                op_code: OpCode::LOP_LOADB,
+                c,
+                ..
+            } if c != 0 => Some(c as isize),
+            // FASTCALL jumps past the CALL at C if the builtin succeeds
+            Self::BC {
+                op_code:
+                    OpCode::LOP_FASTCALL
+                    | OpCode::LOP_FASTCALL1
+                    | OpCode::LOP_FASTCALL2
+                    | OpCode::LOP_FASTCALL2K
+                    | OpCode::LOP_FASTCALL3,
+                c,
+                ..
+            } => Some(c as isize + 1),
+            Self::E {
+                op_code: OpCode::LOP_JUMPX,
+                e,
+            } => Some(e as isize),
+            _ => None,
+        }
+    }
+
+    /// The index of the constant this instruction references, if any
+    pub fn constant_index(&self) -> Option<usize> {
+        match *self {
+            Self::AD {
+                op_code:
+                    OpCode::LOP_LOADK
+                    | OpCode::LOP_GETIMPORT
+                    | OpCode::LOP_DUPTABLE
+                    | OpCode::LOP_DUPCLOSURE,
+                d,
+                ..
+            } => Some(d as usize),
+            Self::AD {
+                op_code: OpCode::LOP_JUMPXEQKN | OpCode::LOP_JUMPXEQKS,
+                aux,
+                ..
+            } => Some((aux & 0xFFFFFF) as usize),
+            Self::AD {
+                op_code: OpCode::LOP_LOADKX,
+                aux,
+                ..
+            }
+            | Self::BC {
+                op_code:
+                    OpCode::LOP_LOADKX
+                    | OpCode::LOP_GETGLOBAL
+                    | OpCode::LOP_SETGLOBAL
+                    | OpCode::LOP_GETTABLEKS
+                    | OpCode::LOP_SETTABLEKS
+                    | OpCode::LOP_NAMECALL
+                    | OpCode::LOP_FASTCALL2K,
+                aux,
+                ..
+            } => Some(aux as usize),
+            Self::BC {
+                op_code:
+                    OpCode::LOP_ADDK
+                    | OpCode::LOP_SUBK
+                    | OpCode::LOP_MULK
+                    | OpCode::LOP_DIVK
+                    | OpCode::LOP_MODK
+                    | OpCode::LOP_POWK
+                    | OpCode::LOP_IDIVK
+                    | OpCode::LOP_ANDK
+                    | OpCode::LOP_ORK,
+                c,
+                ..
+            } => Some(c as usize),
+            Self::BC {
+                op_code: OpCode::LOP_SUBRK | OpCode::LOP_DIVRK,
+                b,
+                ..
+            } => Some(b as usize),
+            _ => None,
         }
     }
 
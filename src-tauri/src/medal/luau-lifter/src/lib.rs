@@ -1,11 +1,15 @@
+mod builtin;
 mod deserializer;
+mod disassembler;
 mod instruction;
 mod lifter;
 mod op_code;
 
 use ast::{
-    local_declarations::LocalDeclarer, name_locals::name_locals, replace_locals::replace_locals,
-    Traverse,
+    compound_assignments::fold_compound_assignments, formatter::Formatter,
+    interpolated_strings::reconstruct_interpolated_strings, local_declarations::LocalDeclarer,
+    name_locals::name_locals, replace_locals::replace_locals, simplify::simplify,
+    type_system::TypeSystem, Traverse,
 };
 
 use by_address::ByAddress;
@@ -13,114 +17,276 @@ use cfg::{
     function::Function,
     ssa::{
         self,
-        structuring::{structure_conditionals, structure_jumps},
+        structuring::{structure_conditionals, structure_if_expressions, structure_jumps},
     },
 };
 use indexmap::IndexMap;
 
+use disassembler::Disassembler;
 use lifter::Lifter;
 
 //use cfg_ir::{dot, function::Function, ssa};
-use clap::Parser;
 use parking_lot::Mutex;
 use petgraph::algo::dominators::simple_fast;
 
 use rustc_hash::FxHashMap;
+use std::{
+    backtrace::Backtrace,
+    cell::{Cell, RefCell},
+    panic::{self, UnwindSafe},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Once,
+    },
+    time::{Duration, Instant},
+};
 use triomphe::Arc;
 
-use deserializer::bytecode::Bytecode;
+use deserializer::{bytecode::Bytecode, function::LPF_NATIVE_MODULE};
+
+pub use ast::{
+    export,
+    formatter::{FormatOptions, IndentationMode, LineMode, QuoteStyle},
+    name_gen::NamingStrategy,
+    roblox::RobloxPasses,
+};
+pub use deserializer::error::{DeserializeError, Reason as DeserializeErrorReason};
+pub use deserializer::DETECT_ENCODE_KEY;
 
 #[cfg(feature = "dhat-heap")]
 #[global_allocator]
 static ALLOC: dhat::Alloc = dhat::Alloc;
 
-#[derive(Parser, Debug)]
-#[clap(about, version, author)]
-struct Args {
-    paths: Vec<String>,
-    /// Number of threads to use (0 = automatic)
-    #[clap(short, long, default_value_t = 0)]
-    threads: usize,
-    /// op = op * key % 256
-    /// For Roblox client bytecode, use 203
-    #[clap(short, long, default_value_t = 1)]
-    key: u8,
-    #[clap(short, long)]
-    recursive: bool,
-    #[clap(short, long)]
-    verbose: bool,
+#[derive(Debug, Clone)]
+pub struct DecompileOptions {
+    /// How the output is printed, including how source line info from the bytecode is used
+    pub format: FormatOptions,
+    /// How locals without debug names are named
+    pub naming: NamingStrategy,
+    /// Annotate locals, parameters and return values with the types they're inferred to have
+    pub infer_types: bool,
+    /// Cleanups for Roblox idioms, each of which can be turned off
+    pub roblox: RobloxPasses,
+    /// Fold constant expressions and remove code that can never run or has no effect
+    pub simplify: bool,
+    /// How much work can be spent on each function before it's given up on
+    pub limits: DecompileLimits,
+}
+
+/// Functions that go over these limits are replaced with a `-- decompilation timed out` stub,
+/// so that pathological control flow can't hang decompilation.
+///
+/// They're checked on every iteration of the structuring passes and of restructuring, and
+/// between the other stages. Lifting the bytecode, destructing SSA and declaring locals aren't
+/// interrupted once started, they take time proportional to the size of the function.
+#[derive(Debug, Clone)]
+pub struct DecompileLimits {
+    /// Wall-clock budget for each function, no limit if `None`
+    pub timeout: Option<Duration>,
+    /// How many times the structuring passes can be repeated on a single function
+    pub max_iterations: usize,
+    /// Once this is set, functions that haven't been decompiled yet are given up on.
+    /// Lets the caller stop decompilation running on another thread
+    pub cancel: Option<std::sync::Arc<AtomicBool>>,
+}
+
+impl Default for DecompileLimits {
+    fn default() -> Self {
+        Self {
+            timeout: None,
+            max_iterations: 1000,
+            cancel: None,
+        }
+    }
+}
+
+impl DecompileLimits {
+    // starts the clock for a function
+    fn budget(&self) -> Budget<'_> {
+        Budget {
+            deadline: self.timeout.map(|timeout| Instant::now() + timeout),
+            max_iterations: self.max_iterations,
+            cancel: self.cancel.as_deref(),
+        }
+    }
+}
+
+struct Budget<'a> {
+    deadline: Option<Instant>,
+    max_iterations: usize,
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl Budget<'_> {
+    fn check(&self) -> Result<(), TimedOut> {
+        if self.deadline.is_some_and(|d| Instant::now() >= d)
+            || self.cancel.is_some_and(|c| c.load(Ordering::Relaxed))
+        {
+            Err(TimedOut)
+        } else {
+            Ok(())
+        }
+    }
 }
 
-pub fn decompile_bytecode(bytecode: &[u8], encode_key: u8) -> Result<String, String> {
+struct TimedOut;
+
+type DecompiledFunction = (ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>);
+
+impl Default for DecompileOptions {
+    fn default() -> Self {
+        Self {
+            format: FormatOptions::default(),
+            naming: NamingStrategy::default(),
+            infer_types: false,
+            roblox: RobloxPasses::default(),
+            simplify: true,
+            limits: DecompileLimits::default(),
+        }
+    }
+}
+
+/// Finds the key the op codes in `bytecode` were encoded with
+pub fn detect_encode_key(bytecode: &[u8]) -> Result<u8, DeserializeError> {
+    deserializer::encode_key::detect(bytecode).map(|(key, _)| key)
+}
+
+/// Pass [`DETECT_ENCODE_KEY`] as `encode_key` to detect it automatically
+pub fn decompile_bytecode(bytecode: &[u8], encode_key: u8) -> Result<String, DeserializeError> {
+    decompile_bytecode_with_options(bytecode, encode_key, &DecompileOptions::default())
+}
+
+pub fn decompile_bytecode_with_options(
+    bytecode: &[u8],
+    encode_key: u8,
+    options: &DecompileOptions,
+) -> Result<String, DeserializeError> {
+    let body = decompile_chunk(bytecode, encode_key, options)?;
+    let mut output = String::new();
+    Formatter::format_with_options(&body, &mut output, options.format).unwrap();
+    Ok(output)
+}
+
+/// Decompiles to a serializable representation of the AST instead of source code.
+/// The same passes run as for [`decompile_bytecode_with_options`], only the format
+/// options are unused.
+pub fn decompile_bytecode_ast(
+    bytecode: &[u8],
+    encode_key: u8,
+    options: &DecompileOptions,
+) -> Result<export::Chunk, DeserializeError> {
+    decompile_chunk(bytecode, encode_key, options).map(|body| export::export(&body))
+}
+
+fn decompile_chunk(
+    bytecode: &[u8],
+    encode_key: u8,
+    options: &DecompileOptions,
+) -> Result<ast::Block, DeserializeError> {
     match deserializer::deserialize(bytecode, encode_key) {
         Ok(chunk) => {
             match chunk {
-                Bytecode::Error(msg) => Err(msg),
+                Bytecode::Error(msg) => Err(DeserializeError::Compilation(msg)),
                 Bytecode::Chunk(chunk) => {
                     let mut lifted = Vec::new();
                     let mut stack = vec![(Arc::<Mutex<ast::Function>>::default(), chunk.main)];
                     while let Some((ast_func, func_id)) = stack.pop() {
-                        let (function, upvalues, child_functions) =
-                            Lifter::lift(&chunk.functions, &chunk.string_table, func_id);
-                        lifted.push((ast_func, function, upvalues));
-                        stack.extend(child_functions.into_iter().map(|(a, f)| (a.0, f)));
+                        let result = catch_panic(|| {
+                            Lifter::lift(
+                                &chunk.functions,
+                                &chunk.string_table,
+                                &chunk.userdata_types,
+                                func_id,
+                            )
+                        });
+                        match result {
+                            Ok((function, upvalues, child_functions)) => {
+                                lifted.push((ast_func, func_id, Ok((function, upvalues))));
+                                stack.extend(child_functions.into_iter().map(|(a, f)| (a.0, f)));
+                            }
+                            // the closures in the function aren't known, so neither are its children
+                            Err(e) => lifted.push((ast_func, func_id, Err(e))),
+                        }
                     }
 
-                    let (main, ..) = lifted.first().unwrap().clone();
+                    let (main, ..) = lifted.first().unwrap();
+                    let main = main.clone();
                     let mut upvalues = lifted
                         .into_iter()
-                        .map(|(ast_function, function, upvalues_in)| {
-                            use std::{backtrace::Backtrace, cell::RefCell, fmt::Write, panic};
+                        .map(|(ast_function, function_id, lifted)| {
+                            use std::fmt::Write;
 
-                            thread_local! {
-                                static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
-                            }
+                            let failure_message = |e: Box<dyn std::any::Any + Send>| {
+                                let panic_information = match e.downcast::<String>() {
+                                    Ok(v) => *v,
+                                    Err(e) => match e.downcast::<&str>() {
+                                        Ok(v) => v.to_string(),
+                                        _ => "Unknown Source of Error".to_owned(),
+                                    },
+                                };
 
-                            let _function_id = function.id;
-                            let mut args = std::panic::AssertUnwindSafe(Some((
-                                ast_function.clone(),
-                                function,
-                                upvalues_in,
-                            )));
-
-                            let prev_hook = panic::take_hook();
-                            panic::set_hook(Box::new(|_| {
-                                let trace = Backtrace::capture();
-                                BACKTRACE.with(move |b| b.borrow_mut().replace(trace));
-                            }));
-                            let result = panic::catch_unwind(move || {
-                                let (ast_function, function, upvalues_in) = args.take().unwrap();
-                                decompile_function(ast_function, function, upvalues_in)
-                            });
-                            panic::set_hook(prev_hook);
-
-                            match result {
-                                Ok(r) => r,
-                                Err(e) => {
-                                    let _panic_information = match e.downcast::<String>() {
-                                        Ok(v) => *v,
-                                        Err(e) => match e.downcast::<&str>() {
-                                            Ok(v) => v.to_string(),
-                                            _ => "Unknown Source of Error".to_owned(),
-                                        },
-                                    };
+                                let mut message = String::new();
+                                writeln!(message, "failed to decompile: {}", panic_information)
+                                    .unwrap();
+                                // if let Some(backtrace) = BACKTRACE.with(|b| b.borrow_mut().take()) {
+                                //     write!(message, "stack backtrace:\n{}", backtrace).unwrap();
+                                // }
+                                writeln!(message, "disassembly:").unwrap();
+                                for line in Disassembler::new(&chunk).instructions(function_id) {
+                                    writeln!(message, "{}", line).unwrap();
+                                }
+                                message
+                            };
+
+                            let (parameters, is_variadic, message) = match lifted {
+                                Ok((function, upvalues_in)) => {
+                                    // kept so the signature can still be printed if decompilation fails
+                                    let parameters = function.parameters.clone();
+                                    let is_variadic = function.is_variadic;
+                                    let mut args = std::panic::AssertUnwindSafe(Some((
+                                        ast_function.clone(),
+                                        function,
+                                        upvalues_in,
+                                    )));
 
-                                    let mut message = String::new();
-                                    writeln!(message, "failed to decompile").unwrap();
-                                    // writeln!(message, "function {} panicked at '{}'", function_id, panic_information).unwrap();
-                                    // if let Some(backtrace) = BACKTRACE.with(|b| b.borrow_mut().take()) {
-                                    //     write!(message, "stack backtrace:\n{}", backtrace).unwrap();
-                                    // }
-
-                                    ast_function.lock().body.extend(
-                                        message
-                                            .trim_end()
-                                            .split('\n')
-                                            .map(|s| ast::Comment::new(s.to_string()).into()),
-                                    );
-                                    (ByAddress(ast_function), Vec::new())
+                                    let budget = options.limits.budget();
+                                    let result = catch_panic(move || {
+                                        let (ast_function, function, upvalues_in) =
+                                            args.take().unwrap();
+                                        decompile_function(
+                                            ast_function,
+                                            function,
+                                            upvalues_in,
+                                            &budget,
+                                        )
+                                    });
+
+                                    let message = match result {
+                                        Ok(Ok(r)) => return r,
+                                        Ok(Err(TimedOut)) => "decompilation timed out".to_string(),
+                                        Err(e) => failure_message(e),
+                                    };
+                                    (parameters, is_variadic, message)
                                 }
+                                Err(e) => (
+                                    Vec::new(),
+                                    chunk.functions[function_id].is_vararg,
+                                    failure_message(e),
+                                ),
+                            };
+
+                            {
+                                let mut ast_function = ast_function.lock();
+                                ast_function.parameters = parameters;
+                                ast_function.is_variadic = is_variadic;
+                                ast_function.body.extend(
+                                    message
+                                        .trim_end()
+                                        .split('\n')
+                                        .map(|s| ast::Comment::new(s.to_string()).into()),
+                                );
                             }
+                            (ByAddress(ast_function), Vec::new())
                         })
                         .collect::<FxHashMap<_, _>>();
 
@@ -128,20 +294,67 @@ pub fn decompile_bytecode(bytecode: &[u8], encode_key: u8) -> Result<String, Str
                     upvalues.remove(&main);
                     let mut body = Arc::try_unwrap(main.0).unwrap().into_inner().body;
                     link_upvalues(&mut body, &mut upvalues);
-                    name_locals(&mut body, true);
-                    Ok(body.to_string())
+                    fold_compound_assignments(&mut body);
+                    reconstruct_interpolated_strings(&mut body);
+                    if options.simplify {
+                        simplify(&mut body);
+                    }
+                    name_locals(&mut body, false, options.naming.name_generator());
+                    options.roblox.run(&mut body);
+                    if options.infer_types {
+                        TypeSystem::analyze(&mut body);
+                    }
+                    if chunk.functions[chunk.main].flags & LPF_NATIVE_MODULE != 0 {
+                        body.insert(0, ast::Comment::new("!native".to_string()).into());
+                    }
+                    Ok(body)
                 }
             }
-        },
-        Err(err) => Err(err.to_string())
+        }
+        Err(err) => Err(err),
     }
 }
 
+pub fn disassemble_bytecode(bytecode: &[u8], encode_key: u8) -> Result<String, DeserializeError> {
+    match deserializer::deserialize(bytecode, encode_key)? {
+        Bytecode::Error(msg) => Err(DeserializeError::Compilation(msg)),
+        Bytecode::Chunk(chunk) => Ok(Disassembler::new(&chunk).disassemble()),
+    }
+}
+
+thread_local! {
+    // whether the panic hook should leave panics on this thread to `catch_panic`
+    static CATCHING_PANIC: Cell<bool> = const { Cell::new(false) };
+    static BACKTRACE: RefCell<Option<Backtrace>> = const { RefCell::new(None) };
+}
+
+// the panic hook is process wide, so it's installed once instead of being swapped around each
+// call, which would race with decompilation running on other threads
+fn catch_panic<T>(f: impl FnOnce() -> T + UnwindSafe) -> std::thread::Result<T> {
+    static INSTALL_HOOK: Once = Once::new();
+    INSTALL_HOOK.call_once(|| {
+        let prev_hook = panic::take_hook();
+        panic::set_hook(Box::new(move |info| {
+            if CATCHING_PANIC.get() {
+                let trace = Backtrace::capture();
+                BACKTRACE.with(move |b| b.borrow_mut().replace(trace));
+            } else {
+                prev_hook(info);
+            }
+        }));
+    });
+    CATCHING_PANIC.set(true);
+    let result = panic::catch_unwind(f);
+    CATCHING_PANIC.set(false);
+    result
+}
+
 fn decompile_function(
     ast_function: Arc<Mutex<ast::Function>>,
     mut function: Function,
     upvalues_in: Vec<ast::RcLocal>,
-) -> (ByAddress<Arc<Mutex<ast::Function>>>, Vec<ast::RcLocal>) {
+    budget: &Budget,
+) -> Result<DecompiledFunction, TimedOut> {
     let (local_count, local_groups, upvalue_in_groups, upvalue_passed_groups) =
         cfg::ssa::construct(&mut function, &upvalues_in);
     let upvalue_to_group = upvalue_in_groups
@@ -167,7 +380,13 @@ fn decompile_function(
     // etc.
     // the macro could also maybe generate an optimal ordering?
     let mut changed = true;
+    let mut iterations = 0;
     while changed {
+        iterations += 1;
+        if iterations > budget.max_iterations {
+            return Err(TimedOut);
+        }
+        budget.check()?;
         changed = false;
 
         let dominators = simple_fast(function.graph(), function.entry().unwrap());
@@ -185,7 +404,10 @@ fn decompile_function(
         {
             changed = true;
         }
-        let mut local_map = FxHashMap::default();
+        if !changed && structure_if_expressions(&mut function) {
+            changed = true;
+        }
+        let mut local_map = IndexMap::new();
         // TODO: loop until returns false?
         if ssa::construct::remove_unnecessary_params(&mut function, &mut local_map) {
             changed = true;
@@ -193,6 +415,7 @@ fn decompile_function(
         ssa::construct::apply_local_map(&mut function, local_map);
     }
     // cfg::dot::render_to(&function, &mut std::io::stdout()).unwrap();
+    budget.check()?;
     ssa::Destructor::new(
         &mut function,
         upvalue_to_group,
@@ -202,8 +425,11 @@ fn decompile_function(
     .destruct();
 
     let params = std::mem::take(&mut function.parameters);
+    let parameter_types = std::mem::take(&mut function.parameter_types);
     let is_variadic = function.is_variadic;
-    let block = Arc::new(restructure::lift(function).into());
+    let block = restructure::lift_until(function, &|| budget.check().is_err()).ok_or(TimedOut)?;
+    let block = Arc::new(block.into());
+    budget.check()?;
     LocalDeclarer::default().declare_locals(
         // TODO: why does block.clone() not work?
         Arc::clone(&block),
@@ -214,9 +440,10 @@ fn decompile_function(
         let mut ast_function = ast_function.lock();
         ast_function.body = Arc::try_unwrap(block).unwrap().into_inner();
         ast_function.parameters = params;
+        ast_function.parameter_types = parameter_types;
         ast_function.is_variadic = is_variadic;
     }
-    (ByAddress(ast_function), upvalues_in)
+    Ok((ByAddress(ast_function), upvalues_in))
 }
 
 fn link_upvalues(
@@ -254,6 +481,9 @@ fn link_upvalues(
             ast::Statement::While(r#while) => {
                 link_upvalues(&mut r#while.block.lock(), upvalues);
             }
+            ast::Statement::Do(r#do) => {
+                link_upvalues(&mut r#do.block.lock(), upvalues);
+            }
             ast::Statement::Repeat(repeat) => {
                 link_upvalues(&mut repeat.block.lock(), upvalues);
             }
@@ -2,6 +2,7 @@ use anyhow::Result;
 
 use by_address::ByAddress;
 
+use indexmap::IndexMap;
 use itertools::Itertools;
 use parking_lot::Mutex;
 use petgraph::stable_graph::NodeIndex;
@@ -10,8 +11,11 @@ use rustc_hash::FxHashMap;
 use triomphe::Arc;
 
 use super::{
+    builtin,
     deserializer::{
-        constant::Constant as BytecodeConstant, function::Function as BytecodeFunction,
+        constant::Constant as BytecodeConstant,
+        function::{Function as BytecodeFunction, LocalVariable, LPF_NATIVE_FUNCTION},
+        type_info::*,
     },
     instruction::Instruction,
     op_code::OpCode,
@@ -25,39 +29,67 @@ use cfg::{
 pub struct Lifter<'a> {
     function_list: &'a Vec<BytecodeFunction>,
     string_table: &'a Vec<Vec<u8>>,
+    userdata_types: &'a Vec<usize>,
     blocks: FxHashMap<usize, NodeIndex>,
     function: Function,
-    child_functions: FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
+    child_functions: IndexMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
     register_map: FxHashMap<usize, ast::RcLocal>,
     constant_map: FxHashMap<usize, ast::Literal>,
     current_node: Option<NodeIndex>,
+    current_pc: usize,
+    // source line of each instruction, empty if the function has no line info
+    instruction_lines: Vec<usize>,
     upvalues: Vec<ast::RcLocal>,
+    // locals from debug info, by register
+    debug_locals: FxHashMap<usize, Vec<DebugLocal>>,
+    // debug locals that have to be copied out of their register when their scope begins, by pc
+    debug_local_starts: FxHashMap<usize, Vec<(usize, ast::RcLocal)>>,
+}
+
+struct DebugLocal {
+    start_pc: usize,
+    end_pc: usize,
+    local: ast::RcLocal,
 }
 
 impl<'a> Lifter<'a> {
     pub fn lift(
         f_list: &'a Vec<BytecodeFunction>,
         str_list: &'a Vec<Vec<u8>>,
+        userdata_types: &'a Vec<usize>,
         function_id: usize,
     ) -> (
         Function,
         Vec<ast::RcLocal>,
-        FxHashMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
+        IndexMap<ByAddress<Arc<Mutex<ast::Function>>>, usize>,
     ) {
-        let mut context = Self {
+        let mut context = Self::new(f_list, str_list, userdata_types, function_id);
+        context.lift_function();
+        (context.function, context.upvalues, context.child_functions)
+    }
+
+    fn new(
+        f_list: &'a Vec<BytecodeFunction>,
+        str_list: &'a Vec<Vec<u8>>,
+        userdata_types: &'a Vec<usize>,
+        function_id: usize,
+    ) -> Self {
+        Self {
             function_list: f_list,
             string_table: str_list,
+            userdata_types,
             blocks: FxHashMap::default(),
             function: Function::new(function_id),
-            child_functions: FxHashMap::default(),
+            child_functions: IndexMap::new(),
             register_map: FxHashMap::default(),
             constant_map: FxHashMap::default(),
             current_node: None,
+            current_pc: 0,
+            instruction_lines: f_list[function_id].instruction_lines().unwrap_or_default(),
             upvalues: Vec::new(),
-        };
-
-        context.lift_function();
-        (context.function, context.upvalues, context.child_functions)
+            debug_locals: FxHashMap::default(),
+            debug_local_starts: FxHashMap::default(),
+        }
     }
 
     fn lift_function(&mut self) {
@@ -101,11 +133,27 @@ impl<'a> Lifter<'a> {
             self.register_map.insert(i as usize, parameter);
         }
 
+        self.function.parameter_types = self.function_list[self.function.id]
+            .parameter_types
+            .iter()
+            .map(|&type_tag| self.type_from_tag(type_tag))
+            .collect();
         self.function.is_variadic = self.function_list[self.function.id].is_vararg;
 
+        self.collect_debug_locals();
+        let scope_entries = self.scope_entry_blocks();
+
         for (start_pc, end_pc) in block_ranges {
             self.current_node = Some(self.block_to_node(start_pc));
-            let (statements, edges) = self.lift_block(start_pc, end_pc);
+            let (statements, mut edges) = self.lift_block(start_pc, end_pc);
+            // only edges from before a scope begins enter it, jumps back to its start
+            // (i.e. a loop header) have to keep the locals' values
+            for (target, _) in &mut edges {
+                match scope_entries.get(target) {
+                    Some(&(scope_start, entry)) if start_pc < scope_start => *target = entry,
+                    _ => {}
+                }
+            }
             let block = self.function.block_mut(self.current_node.unwrap()).unwrap();
             block.0.extend(statements);
             self.function.set_edges(self.current_node.unwrap(), edges);
@@ -235,6 +283,7 @@ impl<'a> Lifter<'a> {
                             .or_insert_with(|| self.function.new_block());
                     }
                 }
+                Instruction::Unknown { .. } => {}
             }
         }
 
@@ -255,7 +304,15 @@ impl<'a> Lifter<'a> {
             .iter()
             .enumerate();
 
+        // statements before this index already have their line set
+        let mut lined_statements = 0;
         while let Some((index, instruction)) = iter.next() {
+            if index != 0 {
+                self.set_lines(&mut statements[lined_statements..]);
+                lined_statements = statements.len();
+            }
+            self.current_pc = block_start + index;
+            statements.extend(self.scope_start_copies(self.current_pc));
             match *instruction {
                 Instruction::BC {
                     op_code,
@@ -313,9 +370,15 @@ impl<'a> Lifter<'a> {
                             .into(),
                         );
                     }
+                    OpCode::LOP_LOADKX => {
+                        let constant = self.constant(aux as _);
+                        let target = self.register(a as _);
+                        let statement = ast::Assign::new(vec![target.into()], vec![constant]);
+                        statements.push(statement.into());
+                    }
                     OpCode::LOP_GETGLOBAL => {
                         let value = self.register(a as _);
-                        let global_name = self.constant(aux as _).into_string().unwrap();
+                        let global_name = self.constant_string(aux as _);
                         statements.push(
                             ast::Assign::new(
                                 vec![value.into()],
@@ -326,7 +389,7 @@ impl<'a> Lifter<'a> {
                     }
                     OpCode::LOP_SETGLOBAL => {
                         let value = self.register(a as _);
-                        let global_name = self.constant(aux as _).into_string().unwrap();
+                        let global_name = self.constant_string(aux as _);
                         statements.push(
                             ast::Assign::new(
                                 vec![ast::Global::new(global_name).into()],
@@ -354,7 +417,7 @@ impl<'a> Lifter<'a> {
                         statements.push(
                             ast::Assign::new(
                                 vec![target.into()],
-                                vec![ast::Index::new(table.into(), key.into()).into()],
+                                vec![ast::Index::new(table.into(), key).into()],
                             )
                             .into(),
                         );
@@ -389,7 +452,7 @@ impl<'a> Lifter<'a> {
                         let key = self.constant(aux as _);
                         statements.push(
                             ast::Assign::new(
-                                vec![ast::Index::new(table.into(), key.into()).into()],
+                                vec![ast::Index::new(table.into(), key).into()],
                                 vec![value.into()],
                             )
                             .into(),
@@ -458,7 +521,7 @@ impl<'a> Lifter<'a> {
                         statements.push(
                             ast::Assign::new(
                                 vec![target.into()],
-                                vec![ast::Binary::new(left.into(), right.into(), op).into()],
+                                vec![ast::Binary::new(left.into(), right, op).into()],
                             )
                             .into(),
                         );
@@ -499,14 +562,21 @@ impl<'a> Lifter<'a> {
                     | OpCode::LOP_FASTCALL1
                     | OpCode::LOP_FASTCALL2
                     | OpCode::LOP_FASTCALL2K
-                    | OpCode::LOP_FASTCALL3 => {}
+                    | OpCode::LOP_FASTCALL3 => {
+                        statements.extend(self.fastcall_fallback(
+                            block_start + index,
+                            op_code,
+                            a,
+                            b,
+                            c,
+                            aux,
+                        ));
+                    }
                     OpCode::LOP_NAMECALL => {
                         let namecall_base = a;
                         let namecall_object = self.register(b as _);
-                        let namecall_method = match self.constant(aux as usize) {
-                            ast::Literal::String(string) => String::from_utf8(string).unwrap(),
-                            _ => unreachable!(),
-                        };
+                        let namecall_method =
+                            String::from_utf8_lossy(&self.constant_string(aux as _)).into_owned();
                         assert!(matches!(
                             iter.next().unwrap().1,
                             Instruction::BC {
@@ -670,7 +740,7 @@ impl<'a> Lifter<'a> {
                             vec![self.register(a as _).into()],
                             vec![ast::Binary::new(
                                 self.register(b as _).into(),
-                                self.constant(c as _).into(),
+                                self.constant(c as _),
                                 ast::BinaryOperation::And,
                             )
                             .into()],
@@ -694,7 +764,7 @@ impl<'a> Lifter<'a> {
                             vec![self.register(a as _).into()],
                             vec![ast::Binary::new(
                                 self.register(b as _).into(),
-                                self.constant(c as _).into(),
+                                self.constant(c as _),
                                 ast::BinaryOperation::Or,
                             )
                             .into()],
@@ -730,7 +800,7 @@ impl<'a> Lifter<'a> {
                         statements.push(
                             ast::Assign::new(
                                 vec![target.into()],
-                                vec![ast::Binary::new(left.into(), right.into(), op).into()],
+                                vec![ast::Binary::new(left, right.into(), op).into()],
                             )
                             .into(),
                         );
@@ -738,11 +808,13 @@ impl<'a> Lifter<'a> {
                     _ => unreachable!("{:?}", instruction),
                 },
                 Instruction::AD { op_code, a, d, aux } => match op_code {
+                    // only ever patched in at runtime to enter native code,
+                    // the function is otherwise executed as usual
+                    OpCode::LOP_NATIVECALL => {}
                     OpCode::LOP_LOADK => {
                         let constant = self.constant(d as _);
                         let target = self.register(a as _);
-                        let statement =
-                            ast::Assign::new(vec![target.into()], vec![constant.into()]);
+                        let statement = ast::Assign::new(vec![target.into()], vec![constant]);
                         statements.push(statement.into());
                     }
                     OpCode::LOP_LOADN => {
@@ -755,28 +827,7 @@ impl<'a> Lifter<'a> {
                     }
                     OpCode::LOP_GETIMPORT => {
                         let target = self.register(a as _);
-                        let import_len = (aux >> 30) & 3;
-                        assert!(import_len <= 3);
-                        let mut import_expression: ast::RValue = ast::Global::new(
-                            self.constant(((aux >> 20) & 1023) as usize)
-                                .into_string()
-                                .unwrap(),
-                        )
-                        .into();
-                        if import_len > 1 {
-                            import_expression = ast::Index::new(
-                                import_expression,
-                                self.constant(((aux >> 10) & 1023) as usize).into(),
-                            )
-                            .into();
-                        }
-                        if import_len > 2 {
-                            import_expression = ast::Index::new(
-                                import_expression,
-                                self.constant((aux & 1023) as usize).into(),
-                            )
-                            .into();
-                        }
+                        let import_expression = self.import(aux as _);
                         let assign = ast::Assign::new(vec![target.into()], vec![import_expression]);
                         statements.push(assign.into());
                     }
@@ -1068,12 +1119,8 @@ impl<'a> Lifter<'a> {
                         let literal = self.constant((aux & ((1 << 24) - 1)) as _);
                         statements.push(
                             ast::If::new(
-                                ast::Binary::new(
-                                    a.into(),
-                                    literal.into(),
-                                    ast::BinaryOperation::Equal,
-                                )
-                                .into(),
+                                ast::Binary::new(a.into(), literal, ast::BinaryOperation::Equal)
+                                    .into(),
                                 ast::Block::default(),
                                 ast::Block::default(),
                             )
@@ -1215,16 +1262,6 @@ impl<'a> Lifter<'a> {
                             },
                             _ => unreachable!(),
                         };
-                        let func_name_index = self.function_list[func_index].function_name;
-                        let func_name = if func_name_index == 0 {
-                            None
-                        } else {
-                            Some(
-                                String::from_utf8_lossy(&self.string_table[func_name_index - 1])
-                                    .into_owned(),
-                            )
-                        };
-
                         let func = &self.function_list[func_index];
                         let mut upvalues_passed = Vec::with_capacity(func.num_upvalues.into());
                         for _ in 0..func.num_upvalues {
@@ -1248,20 +1285,9 @@ impl<'a> Lifter<'a> {
                             upvalues_passed.push(local);
                         }
 
-                        let function = Arc::<Mutex<_>>::default();
-                        self.child_functions
-                            .insert(ByAddress(function.clone()), func_index);
-                        function.lock().name = func_name;
+                        let closure = self.closure(func_index, upvalues_passed);
                         statements.push(
-                            ast::Assign::new(
-                                vec![dest_local.into()],
-                                vec![ast::Closure {
-                                    function: ByAddress(function),
-                                    upvalues: upvalues_passed,
-                                }
-                                .into()],
-                            )
-                            .into(),
+                            ast::Assign::new(vec![dest_local.into()], vec![closure.into()]).into(),
                         );
                     }
                     _ => unreachable!("{:?}", instruction),
@@ -1276,10 +1302,15 @@ impl<'a> Lifter<'a> {
                         ));
                     }
                     _ => unreachable!("{:?}", instruction),
+                },
+                Instruction::Unknown { op_code, .. } => {
+                    statements.push(ast::Comment::new(format!("unknown op {}", op_code)).into());
                 }
             }
         }
 
+        self.set_lines(&mut statements[lined_statements..]);
+
         let last_index = iter
             .next()
             .map(|(i, _)| block_start + i - 1)
@@ -1301,12 +1332,205 @@ impl<'a> Lifter<'a> {
         (statements, edges)
     }
 
+    fn set_lines(&self, statements: &mut [ast::Statement]) {
+        let line = self.instruction_lines.get(self.current_pc).copied();
+        for statement in statements {
+            statement.set_line(line);
+        }
+    }
+
     fn register(&mut self, index: usize) -> ast::RcLocal {
+        if let Some(debug_local) = self.debug_locals.get(&index).and_then(|locals| {
+            locals
+                .iter()
+                .find(|l| (l.start_pc..l.end_pc).contains(&self.current_pc))
+        }) {
+            return debug_local.local.clone();
+        }
         self.register_map.entry(index).or_default().clone()
     }
 
-    fn constant(&mut self, index: usize) -> ast::Literal {
-        let converted_constant = match self.function_list[self.function.id]
+    fn type_from_tag(&self, type_tag: u8) -> ast::type_system::Type {
+        use ast::type_system::Type;
+
+        let r#type = match type_tag & !LBC_TYPE_OPTIONAL_BIT {
+            LBC_TYPE_NIL => Type::Nil,
+            LBC_TYPE_BOOLEAN => Type::Boolean,
+            LBC_TYPE_NUMBER => Type::Number,
+            LBC_TYPE_STRING => Type::String,
+            LBC_TYPE_TABLE => Type::Table {
+                indexer: Some(Box::new((Type::Any, Type::Any))),
+                fields: Default::default(),
+            },
+            LBC_TYPE_FUNCTION => Type::Function(vec![Type::VarArg], vec![Type::VarArg]),
+            LBC_TYPE_THREAD => Type::Thread,
+            // luau has no userdata type, untagged userdata is any host type
+            LBC_TYPE_USERDATA => Type::Any,
+            LBC_TYPE_VECTOR => Type::Vector,
+            LBC_TYPE_BUFFER => Type::Buffer,
+            LBC_TYPE_ANY => Type::Any,
+            tag @ LBC_TYPE_TAGGED_USERDATA_BASE..LBC_TYPE_TAGGED_USERDATA_END => self
+                .userdata_types
+                .get((tag - LBC_TYPE_TAGGED_USERDATA_BASE) as usize)
+                .and_then(|&name| self.string_ref(name))
+                .map_or(Type::Any, Type::Userdata),
+            _ => Type::Any,
+        };
+        if type_tag & LBC_TYPE_OPTIONAL_BIT != 0 && r#type != Type::Any {
+            Type::Optional(Box::new(r#type))
+        } else {
+            r#type
+        }
+    }
+
+    // string references are 1-based, 0 (or an index past the string table) means no string
+    fn string_ref(&self, index: usize) -> Option<String> {
+        let string = self.string_table.get(index.checked_sub(1)?)?;
+        Some(String::from_utf8_lossy(string).into_owned())
+    }
+
+    // every local in the debug info gets its own variable, which registers resolve to
+    // while the current pc is within the local's scope. values written to the register
+    // before the scope begins are copied into the variable at the start of the scope.
+    fn collect_debug_locals(&mut self) {
+        let function = &self.function_list[self.function.id];
+
+        for (upvalue, &name) in self.upvalues.iter().zip(&function.upvalue_names) {
+            upvalue.0 .0.lock().0 = self.string_ref(name);
+        }
+
+        // parameters of variadic functions come into scope after PREPVARARGS
+        let parameters_start = match function.instructions.first() {
+            Some(Instruction::BC {
+                op_code: OpCode::LOP_PREPVARARGS,
+                ..
+            }) => 1,
+            _ => 0,
+        };
+
+        for local_variable in &function.local_variables {
+            if local_variable.start_pc >= local_variable.end_pc {
+                continue;
+            }
+            let register = local_variable.register as usize;
+            let name = self.string_ref(local_variable.name);
+            let (start_pc, local) = if local_variable.start_pc == parameters_start
+                && register < function.num_parameters as usize
+            {
+                let parameter = self.function.parameters[register].clone();
+                parameter.0 .0.lock().0 = name;
+                (local_variable.start_pc, parameter)
+            } else if let Some(closure_pc) = self.closure_initializer(local_variable) {
+                // the closure can capture the local itself (local function), so
+                // the local has to be written directly
+                (closure_pc, ast::RcLocal::new(ast::Local::new(name)))
+            } else {
+                let local = ast::RcLocal::new(ast::Local::new(name));
+                self.debug_local_starts
+                    .entry(local_variable.start_pc)
+                    .or_default()
+                    .push((register, local.clone()));
+                (local_variable.start_pc, local)
+            };
+            self.debug_locals
+                .entry(register)
+                .or_default()
+                .push(DebugLocal {
+                    start_pc,
+                    end_pc: local_variable.end_pc,
+                    local,
+                });
+        }
+    }
+
+    // copies the values of debug locals whose scope begins at `pc` out of their registers
+    fn scope_start_copies(&mut self, pc: usize) -> Vec<ast::Statement> {
+        let Some(starts) = self.debug_local_starts.remove(&pc) else {
+            return Vec::new();
+        };
+        starts
+            .into_iter()
+            .map(|(register, local)| {
+                let register_local = self.register_map.entry(register).or_default().clone();
+                ast::Assign::new(vec![local.into()], vec![register_local.into()]).into()
+            })
+            .collect()
+    }
+
+    // scopes that begin at the start of a block, which can be jumped to from within the
+    // scope, are entered through a block of their own that does the copies. loop variables
+    // are the exception, they're copied out every iteration. returns the pc the scope
+    // begins at and that block, by the node of the block the scope begins at.
+    fn scope_entry_blocks(&mut self) -> FxHashMap<NodeIndex, (usize, NodeIndex)> {
+        let instructions = &self.function_list[self.function.id].instructions;
+        let mut scope_starts = self
+            .debug_local_starts
+            .keys()
+            .copied()
+            .filter(|&pc| {
+                pc != 0
+                    && self.blocks.contains_key(&pc)
+                    && !matches!(
+                        instructions[pc - 1].op_code(),
+                        Some(
+                            OpCode::LOP_FORNPREP
+                                | OpCode::LOP_FORGPREP
+                                | OpCode::LOP_FORGPREP_INEXT
+                                | OpCode::LOP_FORGPREP_NEXT
+                        )
+                    )
+            })
+            .collect::<Vec<_>>();
+        scope_starts.sort_unstable();
+
+        let mut scope_entries = FxHashMap::default();
+        for pc in scope_starts {
+            self.current_pc = pc;
+            let mut statements = self.scope_start_copies(pc);
+            self.set_lines(&mut statements);
+            let entry = self.function.new_block();
+            self.function.block_mut(entry).unwrap().0.extend(statements);
+            let node = self.block_to_node(pc);
+            self.function.set_edges(
+                entry,
+                vec![(node, BlockEdge::new(BranchType::Unconditional))],
+            );
+            scope_entries.insert(node, (pc, entry));
+        }
+        scope_entries
+    }
+
+    // returns the pc of the closure that initializes a local, if the local's scope
+    // begins right after it is created and captured
+    fn closure_initializer(&self, local_variable: &LocalVariable) -> Option<usize> {
+        let instructions = &self.function_list[self.function.id].instructions;
+        let mut pc = local_variable.start_pc.checked_sub(1)?;
+        while let Instruction::BC {
+            op_code: OpCode::LOP_CAPTURE,
+            ..
+        } = instructions.get(pc)?
+        {
+            pc = pc.checked_sub(1)?;
+        }
+        match *instructions.get(pc)? {
+            Instruction::AD {
+                op_code: OpCode::LOP_NEWCLOSURE | OpCode::LOP_DUPCLOSURE,
+                a,
+                ..
+            } if a == local_variable.register
+                && !(pc + 1..=local_variable.start_pc).any(|pc| self.blocks.contains_key(&pc)) =>
+            {
+                Some(pc)
+            }
+            _ => None,
+        }
+    }
+
+    fn constant(&mut self, index: usize) -> ast::RValue {
+        if let Some(literal) = self.constant_map.get(&index) {
+            return literal.clone().into();
+        }
+        let literal = match self.function_list[self.function.id]
             .constants
             .get(index)
             .unwrap()
@@ -1319,12 +1543,179 @@ impl<'a> Lifter<'a> {
                 ast::Literal::String(self.string_table[*v - 1].clone())
             }
             BytecodeConstant::Vector(x, y, z, _) => ast::Literal::Vector(*x, *y, *z),
-            _ => unimplemented!(),
+            &BytecodeConstant::Import(id) => return self.import(id),
+            // the shape of a table constructor, the values are set by the following instructions
+            BytecodeConstant::Table(keys) => {
+                let constants = &self.function_list[self.function.id].constants;
+                return ast::Table(
+                    keys.iter()
+                        .map(|&key| {
+                            // the deserializer only accepts string and number keys
+                            let key = match constants[key] {
+                                BytecodeConstant::String(v) => {
+                                    ast::Literal::String(self.string_table[v - 1].clone())
+                                }
+                                BytecodeConstant::Number(v) => ast::Literal::Number(v),
+                                _ => unreachable!(),
+                            };
+                            (Some(key.into()), ast::Literal::Nil.into())
+                        })
+                        .collect(),
+                )
+                .into();
+            }
+            &BytecodeConstant::Closure(func_index) => {
+                return self.closure(func_index, Vec::new()).into();
+            }
         };
-        self.constant_map
-            .entry(index)
-            .or_insert(converted_constant)
-            .clone()
+        self.constant_map.insert(index, literal.clone());
+        literal.into()
+    }
+
+    fn constant_string(&mut self, index: usize) -> Vec<u8> {
+        match self.constant(index) {
+            ast::RValue::Literal(ast::Literal::String(string)) => string,
+            constant => panic!(
+                "expected constant {} to be a string, got {}",
+                index, constant
+            ),
+        }
+    }
+
+    // an import is up to 3 constant indices of 10 bits each, the number of indices is in the top 2 bits
+    fn import(&mut self, id: usize) -> ast::RValue {
+        let import_len = (id >> 30) & 3;
+        let mut import_expression: ast::RValue =
+            ast::Global::new(self.constant_string((id >> 20) & 1023)).into();
+        if import_len > 1 {
+            import_expression =
+                ast::Index::new(import_expression, self.constant((id >> 10) & 1023)).into();
+        }
+        if import_len > 2 {
+            import_expression = ast::Index::new(import_expression, self.constant(id & 1023)).into();
+        }
+        import_expression
+    }
+
+    fn builtin(name: &str) -> ast::RValue {
+        let mut path = name.split('.');
+        let mut expression: ast::RValue =
+            ast::Global::new(path.next().unwrap().as_bytes().to_vec()).into();
+        for key in path {
+            expression = ast::Index::new(
+                expression,
+                ast::Literal::String(key.as_bytes().to_vec()).into(),
+            )
+            .into();
+        }
+        expression
+    }
+
+    // the fallback path of a FASTCALL loads the function and its arguments for the CALL
+    // that follows it, this fills in whatever it doesn't, e.g. because an obfuscator
+    // stripped the function load or the argument was only given as the constant operand
+    fn fastcall_fallback(
+        &mut self,
+        pc: usize,
+        op_code: OpCode,
+        builtin: u8,
+        b: u8,
+        skip: u8,
+        aux: u32,
+    ) -> Vec<ast::Statement> {
+        let function_list = self.function_list;
+        let function = &function_list[self.function.id];
+        let call_pc = pc + 1 + skip as usize;
+        let Some(&Instruction::BC {
+            op_code: OpCode::LOP_CALL,
+            a: base,
+            ..
+        }) = function.instructions.get(call_pc)
+        else {
+            return Vec::new();
+        };
+        let fallback = &function.instructions[pc + 1..call_pc];
+        let is_written = |register: u8| {
+            fallback.iter().any(|instruction| match *instruction {
+                Instruction::BC { op_code, a, .. } | Instruction::AD { op_code, a, .. } => {
+                    op_code != OpCode::LOP_NOP && a == register
+                }
+                _ => false,
+            })
+        };
+
+        let mut registers = Vec::new();
+        let mut constants = Vec::new();
+        if op_code != OpCode::LOP_FASTCALL {
+            registers.push(b);
+        }
+        match op_code {
+            OpCode::LOP_FASTCALL2 => registers.push(aux as u8),
+            OpCode::LOP_FASTCALL3 => registers.extend([aux as u8, (aux >> 8) as u8]),
+            OpCode::LOP_FASTCALL2K if builtin == builtin::LBF_BIT32_EXTRACTK => {
+                if let Some(&BytecodeConstant::Number(packed)) =
+                    function.constants.get(aux as usize)
+                {
+                    let packed = packed as u32;
+                    constants.push(ast::Literal::Number((packed & 31) as f64).into());
+                    constants.push(ast::Literal::Number(((packed >> 5) + 1) as f64).into());
+                }
+            }
+            OpCode::LOP_FASTCALL2K => constants.push(self.constant(aux as usize)),
+            _ => {}
+        }
+
+        let mut statements = Vec::new();
+        if !is_written(base) {
+            if let Some(name) = builtin::builtin_name(builtin) {
+                statements.push(
+                    ast::Assign::new(
+                        vec![self.register(base as _).into()],
+                        vec![Self::builtin(name)],
+                    )
+                    .into(),
+                );
+            }
+        }
+        // the arguments go in the registers after the function, stop if malformed bytecode
+        // runs out of them
+        let mut targets = (base..=u8::MAX).skip(1);
+        for (register, target) in registers.into_iter().zip(&mut targets) {
+            if register != target && !is_written(target) {
+                statements.push(
+                    ast::Assign::new(
+                        vec![self.register(target as _).into()],
+                        vec![self.register(register as _).into()],
+                    )
+                    .into(),
+                );
+            }
+        }
+        for (constant, target) in constants.into_iter().zip(targets) {
+            if !is_written(target) {
+                statements.push(
+                    ast::Assign::new(vec![self.register(target as _).into()], vec![constant])
+                        .into(),
+                );
+            }
+        }
+        statements
+    }
+
+    fn closure(&mut self, func_index: usize, upvalues: Vec<ast::Upvalue>) -> ast::Closure {
+        let function = Arc::<Mutex<_>>::default();
+        self.child_functions
+            .insert(ByAddress(function.clone()), func_index);
+        {
+            let mut function = function.lock();
+            let bytecode_function = &self.function_list[func_index];
+            function.name = self.string_ref(bytecode_function.function_name);
+            function.is_native = bytecode_function.flags & LPF_NATIVE_FUNCTION != 0;
+        }
+        ast::Closure {
+            function: ByAddress(function),
+            upvalues,
+        }
     }
 
     fn block_to_node(&self, insn_index: usize) -> NodeIndex {
@@ -1362,6 +1753,151 @@ impl<'a> Lifter<'a> {
                     | OpCode::LOP_FORGPREP_NEXT
             ),
             Instruction::E { op_code, .. } => matches!(op_code, OpCode::LOP_JUMPX),
+            Instruction::Unknown { .. } => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytecode_function(constants: Vec<BytecodeConstant>) -> BytecodeFunction {
+        BytecodeFunction {
+            max_stack_size: 0,
+            num_parameters: 0,
+            num_upvalues: 0,
+            is_vararg: false,
+            flags: 0,
+            parameter_types: Vec::new(),
+            instructions: Vec::new(),
+            constants,
+            functions: Vec::new(),
+            line_defined: 0,
+            function_name: 0,
+            line_gap_log2: None,
+            line_info_delta: None,
+            abs_line_info_delta: None,
+            local_variables: Vec::new(),
+            upvalue_names: Vec::new(),
         }
     }
+
+    fn strings() -> Vec<Vec<u8>> {
+        ["game", "Workspace", "Part", "name", "helper"]
+            .into_iter()
+            .map(|s| s.as_bytes().to_vec())
+            .collect()
+    }
+
+    // lifts the constant at `index` of the first function
+    fn lift_constant(functions: &Vec<BytecodeFunction>, index: usize) -> String {
+        let strings = strings();
+        let userdata_types = Vec::new();
+        let mut lifter = Lifter::new(functions, &strings, &userdata_types, 0);
+        lifter.constant(index).to_string()
+    }
+
+    #[test]
+    fn nil_constant() {
+        let functions = vec![bytecode_function(vec![BytecodeConstant::Nil])];
+        assert_eq!(lift_constant(&functions, 0), "nil");
+    }
+
+    #[test]
+    fn boolean_constant() {
+        let functions = vec![bytecode_function(vec![
+            BytecodeConstant::Boolean(true),
+            BytecodeConstant::Boolean(false),
+        ])];
+        assert_eq!(lift_constant(&functions, 0), "true");
+        assert_eq!(lift_constant(&functions, 1), "false");
+    }
+
+    #[test]
+    fn number_constant() {
+        let functions = vec![bytecode_function(vec![BytecodeConstant::Number(1.5)])];
+        assert_eq!(lift_constant(&functions, 0), "1.5");
+    }
+
+    #[test]
+    fn string_constant() {
+        let functions = vec![bytecode_function(vec![BytecodeConstant::String(4)])];
+        assert_eq!(lift_constant(&functions, 0), "\"name\"");
+    }
+
+    #[test]
+    fn vector_constant() {
+        let functions = vec![bytecode_function(vec![BytecodeConstant::Vector(
+            1.0, 2.0, 3.0, 0.0,
+        )])];
+        assert_eq!(lift_constant(&functions, 0), "Vector3.new(1, 2, 3)");
+    }
+
+    #[test]
+    fn import_constant() {
+        let functions = vec![bytecode_function(vec![
+            BytecodeConstant::String(1),
+            BytecodeConstant::String(2),
+            BytecodeConstant::String(3),
+            BytecodeConstant::Import(1 << 30),
+            BytecodeConstant::Import(3 << 30 | 1 << 10 | 2),
+        ])];
+        assert_eq!(lift_constant(&functions, 3), "game");
+        assert_eq!(lift_constant(&functions, 4), "game.Workspace.Part");
+    }
+
+    #[test]
+    fn table_constant() {
+        let functions = vec![bytecode_function(vec![
+            BytecodeConstant::String(4),
+            BytecodeConstant::Table(vec![0]),
+            BytecodeConstant::Table(Vec::new()),
+        ])];
+        assert_eq!(lift_constant(&functions, 1), "{\n\t[\"name\"] = nil\n}");
+        assert_eq!(lift_constant(&functions, 2), "{}");
+    }
+
+    #[test]
+    fn closure_constant() {
+        let mut child = bytecode_function(Vec::new());
+        child.function_name = 5;
+        let functions = vec![bytecode_function(vec![BytecodeConstant::Closure(1)]), child];
+        let strings = strings();
+        let userdata_types = Vec::new();
+        let mut lifter = Lifter::new(&functions, &strings, &userdata_types, 0);
+        let closure = lifter.constant(0).into_closure().unwrap();
+        assert_eq!(closure.function.lock().name.as_deref(), Some("helper"));
+        assert!(closure.upvalues.is_empty());
+        assert_eq!(lifter.child_functions.get(&closure.function), Some(&1));
+    }
+
+    #[test]
+    fn fastcall_fallback_out_of_registers() {
+        // math.max(r0, r1, r2) with the function in the second to last register, so only one of
+        // the arguments fits
+        let mut function = bytecode_function(Vec::new());
+        function.instructions = vec![
+            Instruction::BC {
+                op_code: OpCode::LOP_FASTCALL3,
+                a: 18,
+                b: 0,
+                c: 1,
+                aux: 1 | 2 << 8,
+            },
+            Instruction::BC {
+                op_code: OpCode::LOP_CALL,
+                a: 254,
+                b: 4,
+                c: 2,
+                aux: 0,
+            },
+        ];
+        let functions = vec![function];
+        let strings = strings();
+        let userdata_types = Vec::new();
+        let mut lifter = Lifter::new(&functions, &strings, &userdata_types, 0);
+        let statements = lifter.fastcall_fallback(0, OpCode::LOP_FASTCALL3, 18, 0, 0, 1 | 2 << 8);
+        assert_eq!(statements.len(), 2);
+    }
 }
@@ -1,10 +1,179 @@
-fn main() {
-    let file_name = std::env::args().nth(1).expect("expected exactly one file");
-    let key = std::env::args()
-        .nth(2)
-        .or_else(|| None)
-        .map(|s| if s == "-e" { 203 } else { panic!() })
-        .unwrap_or(1);
-    let bytecode = std::fs::read(file_name).expect("failed to read file");
-    println!("{}", luau_lifter::decompile_bytecode(&bytecode, key));
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+use clap::Parser;
+use rayon::prelude::*;
+use walkdir::WalkDir;
+
+// files with these extensions are picked up when walking a directory,
+// files passed directly are always decompiled
+const BYTECODE_EXTENSIONS: &[&str] = &["luac", "luauc", "bc", "bin"];
+
+#[derive(Parser, Debug)]
+#[clap(about, version, author)]
+struct Args {
+    /// Bytecode files or directories containing bytecode files
+    #[clap(required = true)]
+    paths: Vec<PathBuf>,
+    /// Number of threads to use (0 = automatic)
+    #[clap(short, long, default_value_t = 0)]
+    threads: usize,
+    /// op = op * key % 256
+    /// For Roblox client bytecode, use 203 (0 = automatic)
+    #[clap(short, long, default_value_t = luau_lifter::DETECT_ENCODE_KEY)]
+    key: u8,
+    /// Also decompile files in subdirectories
+    #[clap(short, long)]
+    recursive: bool,
+    /// Write output files into this directory instead of next to the inputs
+    #[clap(short, long)]
+    output: Option<PathBuf>,
+    /// Write a disassembly instead of decompiling
+    #[clap(short, long)]
+    disassemble: bool,
+    /// Print every file as it is decompiled
+    #[clap(short, long)]
+    verbose: bool,
+}
+
+struct Input {
+    path: PathBuf,
+    // the path of the output file relative to the output directory
+    relative_path: PathBuf,
+}
+
+fn collect_inputs(args: &Args) -> Vec<Input> {
+    let mut inputs = Vec::new();
+    for root in &args.paths {
+        if !root.is_dir() {
+            inputs.push(Input {
+                path: root.clone(),
+                relative_path: root.file_name().map(PathBuf::from).unwrap_or_default(),
+            });
+            continue;
+        }
+
+        let walker = WalkDir::new(root)
+            .max_depth(if args.recursive { usize::MAX } else { 1 })
+            .sort_by_file_name();
+        for entry in walker {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(err) => {
+                    eprintln!("{}", err);
+                    continue;
+                }
+            };
+            if entry.file_type().is_file() && is_bytecode_path(entry.path()) {
+                inputs.push(Input {
+                    path: entry.path().to_path_buf(),
+                    relative_path: entry.path().strip_prefix(root).unwrap().to_path_buf(),
+                });
+            }
+        }
+    }
+    inputs
+}
+
+fn is_bytecode_path(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| BYTECODE_EXTENSIONS.contains(&e))
+}
+
+fn output_path(args: &Args, input: &Input) -> PathBuf {
+    let extension = if args.disassemble { "txt" } else { "lua" };
+    match &args.output {
+        Some(output) => output.join(&input.relative_path).with_extension(extension),
+        None => input.path.with_extension(extension),
+    }
+}
+
+// an output path that doesn't exist yet can't be the input
+fn is_same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+fn process(args: &Args, input: &Input) -> Result<PathBuf, String> {
+    let output_path = output_path(args, input);
+    if is_same_file(&output_path, &input.path) {
+        return Err(
+            "output would overwrite the input, use --output to write it elsewhere".to_string(),
+        );
+    }
+
+    let bytecode = fs::read(&input.path).map_err(|e| e.to_string())?;
+    let result = std::panic::catch_unwind(|| {
+        if args.disassemble {
+            luau_lifter::disassemble_bytecode(&bytecode, args.key)
+        } else {
+            luau_lifter::decompile_bytecode(&bytecode, args.key)
+        }
+    })
+    .map_err(|e| match e.downcast::<String>() {
+        Ok(message) => format!("panicked: {}", message),
+        Err(e) => match e.downcast::<&str>() {
+            Ok(message) => format!("panicked: {}", message),
+            Err(_) => "panicked".to_string(),
+        },
+    })?
+    .map_err(|e| e.to_string())?;
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    fs::write(&output_path, result).map_err(|e| e.to_string())?;
+    Ok(output_path)
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse();
+    rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads)
+        .build_global()?;
+
+    let inputs = collect_inputs(&args);
+    let start = Instant::now();
+    let results = inputs
+        .par_iter()
+        .map(|input| {
+            let start = Instant::now();
+            let result = process(&args, input);
+            let duration = start.elapsed();
+            match &result {
+                Ok(output_path) if args.verbose => println!(
+                    "{} -> {} ({:?})",
+                    input.path.display(),
+                    output_path.display(),
+                    duration
+                ),
+                Ok(_) => {}
+                Err(err) => eprintln!("{}: {}", input.path.display(), err),
+            }
+            (result.is_ok(), duration)
+        })
+        .collect::<Vec<_>>();
+
+    let succeeded = results.iter().filter(|(ok, _)| *ok).count();
+    let failed = results.len() - succeeded;
+    let total = results.iter().map(|(_, d)| *d).sum::<Duration>();
+    let slowest = results.iter().map(|(_, d)| *d).max().unwrap_or_default();
+    println!(
+        "{} succeeded, {} failed in {:?} ({:?} total, {:?} slowest)",
+        succeeded,
+        failed,
+        start.elapsed(),
+        total,
+        slowest
+    );
+    if failed != 0 {
+        std::process::exit(1);
+    }
+    Ok(())
 }
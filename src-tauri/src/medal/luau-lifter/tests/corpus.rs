@@ -0,0 +1,145 @@
+// Every source in `tests/fixtures` is compiled with debug level 1 at each optimization level,
+// `<name>.O<level>.bc`, again with its op codes encoded the way Roblox does it,
+// `<name>.O<level>.key203.bc`, and with debug level 2 so that locals keep their names,
+// `<name>.O<level>.g2.bc`.
+// Run with `INSTA_UPDATE=always` (or use `cargo insta review`) to accept changed output.
+
+use std::{fs, path::Path};
+
+use luau_lifter::{
+    decompile_bytecode, decompile_bytecode_with_options, detect_encode_key, disassemble_bytecode,
+    DecompileOptions, LineMode,
+};
+use mlua::Lua;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+const OPTIMIZATION_LEVELS: [u8; 3] = [0, 1, 2];
+
+fn sources() -> Vec<String> {
+    let mut names = fs::read_dir(FIXTURES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "luau"))
+        .map(|path| path.file_stem().unwrap().to_string_lossy().into_owned())
+        .collect::<Vec<_>>();
+    names.sort();
+    assert!(!names.is_empty());
+    names
+}
+
+fn decompile(file_name: &str, encode_key: u8) -> String {
+    let bytecode = fs::read(Path::new(FIXTURES).join(file_name))
+        .unwrap_or_else(|e| panic!("{}: {}", file_name, e));
+    let output = decompile_bytecode(&bytecode, encode_key)
+        .unwrap_or_else(|e| panic!("{}: {}", file_name, e));
+    assert!(
+        !output.contains("failed to decompile"),
+        "{}:\n{}",
+        file_name,
+        output
+    );
+    output
+}
+
+#[test]
+fn corpus() {
+    for name in sources() {
+        for level in OPTIMIZATION_LEVELS {
+            let fixture = format!("{}.O{}", name, level);
+            let output = decompile(&format!("{}.bc", fixture), 1);
+            assert_eq!(
+                output,
+                decompile(&format!("{}.key203.bc", fixture), 203),
+                "{}: encoded bytecode decompiled differently",
+                fixture
+            );
+            insta::assert_snapshot!(fixture.as_str(), output);
+            insta::assert_snapshot!(
+                format!("{}.g2", fixture),
+                decompile(&format!("{}.g2.bc", fixture), 1)
+            );
+        }
+    }
+}
+
+#[test]
+fn detects_encode_key() {
+    for name in sources() {
+        for level in OPTIMIZATION_LEVELS {
+            for (file_name, encode_key) in [
+                (format!("{}.O{}.bc", name, level), 1),
+                (format!("{}.O{}.g2.bc", name, level), 1),
+                (format!("{}.O{}.key203.bc", name, level), 203),
+            ] {
+                let bytecode = fs::read(Path::new(FIXTURES).join(&file_name)).unwrap();
+                assert_eq!(
+                    detect_encode_key(&bytecode).unwrap(),
+                    encode_key,
+                    "{}",
+                    file_name
+                );
+            }
+        }
+    }
+}
+
+#[test]
+fn line_modes() {
+    let bytecode = fs::read(Path::new(FIXTURES).join("control.O0.g2.bc")).unwrap();
+    for (suffix, line_mode) in [("comment", LineMode::Comment), ("layout", LineMode::Layout)] {
+        let mut options = DecompileOptions::default();
+        options.format.line_mode = line_mode;
+        insta::assert_snapshot!(
+            format!("control.O0.g2.{}", suffix),
+            decompile_bytecode_with_options(&bytecode, 1, &options).unwrap()
+        );
+    }
+}
+
+#[test]
+fn inferred_types() {
+    let options = DecompileOptions {
+        infer_types: true,
+        ..Default::default()
+    };
+    for name in ["roblox", "types"] {
+        for level in OPTIMIZATION_LEVELS {
+            let fixture = format!("{}.O{}.g2", name, level);
+            let bytecode = fs::read(Path::new(FIXTURES).join(format!("{}.bc", fixture))).unwrap();
+            insta::assert_snapshot!(
+                format!("{}.inferred", fixture),
+                decompile_bytecode_with_options(&bytecode, 1, &options).unwrap()
+            );
+        }
+    }
+}
+
+// the native flags only show up in the disassembly
+#[test]
+fn native_flags() {
+    let bytecode = fs::read(Path::new(FIXTURES).join("native.O1.bc")).unwrap();
+    insta::assert_snapshot!(
+        "native.O1.disassembly",
+        disassemble_bytecode(&bytecode, 1).unwrap()
+    );
+}
+
+#[test]
+fn output_is_valid_luau() {
+    // the fixtures are compiled with every Luau flag on, `@native` needs this one to parse
+    Lua::set_fflag("LuauNativeAttribute", true).unwrap();
+    let lua = Lua::new();
+    for name in sources() {
+        for level in OPTIMIZATION_LEVELS {
+            for fixture in [
+                format!("{}.O{}.bc", name, level),
+                format!("{}.O{}.g2.bc", name, level),
+            ] {
+                let output = decompile(&fixture, 1);
+                if let Err(err) = lua.load(&output).set_name(&fixture).into_function() {
+                    panic!("{} doesn't parse: {}\n{}", fixture, err, output);
+                }
+            }
+        }
+    }
+}
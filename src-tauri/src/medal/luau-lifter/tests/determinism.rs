@@ -0,0 +1,55 @@
+use std::thread;
+
+use luau_lifter::{decompile_bytecode_with_options, DecompileOptions};
+use mlua::Compiler;
+
+// a mix of locals, upvalues and loops, which is where ordering by address used to show up
+const SOURCE: &str = r#"local counter = 0
+local items = {}
+local function add(name, count)
+	local entry = { name = name, count = count }
+	table.insert(items, entry)
+	counter += count
+	return function()
+		entry.count -= 1
+		counter -= 1
+		return entry.count
+	end
+end
+for i = 1, 3 do
+	local take = add("item" .. i, i)
+	while take() > 0 do
+		if counter % 2 == 0 then
+			print(counter)
+		end
+	end
+end
+for _, entry in items do
+	print(entry.name, entry.count)
+end"#;
+
+fn decompile(bytecode: &[u8], options: &DecompileOptions) -> String {
+    decompile_bytecode_with_options(bytecode, 1, options).unwrap()
+}
+
+// locals are compared by address, so the second run is on another thread
+// to make it unlikely that anything is allocated at the same address
+#[test]
+fn output_is_deterministic() {
+    for level in 0..=2 {
+        let bytecode = Compiler::new()
+            .set_optimization_level(level)
+            .set_debug_level(1)
+            .compile(SOURCE);
+        for infer_types in [false, true] {
+            let options = DecompileOptions {
+                infer_types,
+                ..Default::default()
+            };
+            let first = decompile(&bytecode, &options);
+            let second =
+                thread::scope(|s| s.spawn(|| decompile(&bytecode, &options)).join()).unwrap();
+            assert_eq!(first, second, "O{}", level);
+        }
+    }
+}
@@ -0,0 +1,152 @@
+// Tools match locals across exports by their ids and the locals closures capture, so those have
+// to be as stable as the text output.
+
+use std::{fs, path::Path, thread};
+
+use luau_lifter::{decompile_bytecode_ast, DecompileOptions, DETECT_ENCODE_KEY};
+use serde_json::Value;
+
+const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures");
+
+fn export(bytecode: &[u8]) -> Value {
+    let chunk =
+        decompile_bytecode_ast(bytecode, DETECT_ENCODE_KEY, &DecompileOptions::default()).unwrap();
+    serde_json::to_value(chunk).unwrap()
+}
+
+fn export_fixture(file_name: &str) -> Value {
+    export(&fs::read(Path::new(FIXTURES).join(file_name)).unwrap())
+}
+
+// every object in `value`, including `value` itself
+fn objects<'a>(value: &'a Value, objects_found: &mut Vec<&'a Value>) {
+    match value {
+        Value::Object(object) => {
+            objects_found.push(value);
+            for value in object.values() {
+                objects(value, objects_found);
+            }
+        }
+        Value::Array(array) => {
+            for value in array {
+                objects(value, objects_found);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn local_id(chunk: &Value, name: &str) -> u64 {
+    let locals = chunk["locals"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|local| local["name"] == name)
+        .collect::<Vec<_>>();
+    assert_eq!(locals.len(), 1, "{}", name);
+    locals[0]["id"].as_u64().unwrap()
+}
+
+#[test]
+fn export_is_deterministic() {
+    let mut paths = fs::read_dir(FIXTURES)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|e| e == "bc"))
+        .collect::<Vec<_>>();
+    paths.sort();
+    for path in paths {
+        let bytecode = fs::read(&path).unwrap();
+        let first = serde_json::to_string(&export(&bytecode)).unwrap();
+        // on another thread, so that locals are unlikely to be allocated at the same addresses
+        let second = thread::scope(|s| s.spawn(|| export(&bytecode)).join()).unwrap();
+        assert_eq!(
+            first,
+            serde_json::to_string(&second).unwrap(),
+            "{}",
+            path.display()
+        );
+    }
+}
+
+#[test]
+fn local_ids() {
+    let chunk = export_fixture("closures.O0.g2.bc");
+    let locals = chunk["locals"].as_array().unwrap();
+    assert!(!locals.is_empty());
+    for (id, local) in locals.iter().enumerate() {
+        assert_eq!(local["id"], id);
+    }
+    // ids are assigned in the order locals are first encountered
+    assert_eq!(local_id(&chunk, "counter"), 0);
+    assert_eq!(local_id(&chunk, "start"), 1);
+    assert_eq!(local_id(&chunk, "step"), 2);
+
+    let mut found = Vec::new();
+    objects(&chunk["body"], &mut found);
+    let references = found
+        .into_iter()
+        .filter(|object| object.get("id").is_some())
+        .collect::<Vec<_>>();
+    assert!(!references.is_empty());
+    for reference in references {
+        let local = &locals[reference["id"].as_u64().unwrap() as usize];
+        assert_eq!(reference["name"], local["name"]);
+    }
+}
+
+#[test]
+fn closure_upvalues() {
+    let chunk = export_fixture("closures.O0.g2.bc");
+    let mut found = Vec::new();
+    objects(&chunk["body"], &mut found);
+    let closure = |name: &str| {
+        found
+            .iter()
+            .find(|object| object["kind"] == "closure" && object["function"]["name"] == name)
+            .unwrap_or_else(|| panic!("no closure named {}", name))
+    };
+    let upvalues = |name: &str| {
+        closure(name)["upvalues"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|upvalue| {
+                (
+                    upvalue["kind"].as_str().unwrap().to_string(),
+                    upvalue["local"]["id"].as_u64().unwrap(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let count = local_id(&chunk, "count");
+    let step = local_id(&chunk, "step");
+    let history = local_id(&chunk, "history");
+    // `count` and `history` are assigned after they're captured, `step` isn't
+    assert_eq!(
+        upvalues("increment"),
+        [
+            ("ref".to_string(), count),
+            ("copy".to_string(), step),
+            ("ref".to_string(), history),
+        ]
+    );
+    assert!(upvalues("counter").is_empty());
+}
+
+#[test]
+fn raw_strings() {
+    let chunk = export_fixture("strings.O0.g2.bc");
+    let mut found = Vec::new();
+    objects(&chunk["body"], &mut found);
+    let strings = found
+        .into_iter()
+        .filter(|object| object["type"] == "string")
+        .map(|object| &object["value"])
+        .collect::<Vec<_>>();
+    // "\xff\xfe\u{48}" isn't UTF-8, so it's exported as bytes rather than with replacement
+    // characters that other strings could have too
+    assert!(strings.contains(&&Value::from(vec![0xffu8, 0xfe, b'H'])));
+    assert!(strings.iter().all(|s| s.is_string() || s.is_array()));
+}
@@ -0,0 +1,61 @@
+// Compiles a fixture for generate.sh, the compiler is called directly since `luau-compile`
+// can't set userdata types or encode op codes.
+//
+// usage: compile <in.luau> <out.bc> <optimization level> <debug level> [key]
+#include "Luau/BytecodeBuilder.h"
+#include "Luau/BytecodeUtils.h"
+#include "Luau/Common.h"
+#include "Luau/Compiler.h"
+
+#include <cstdlib>
+#include <cstring>
+#include <fstream>
+#include <sstream>
+
+// multiplies every op code by the key, like Roblox does with 227
+struct KeyEncoder : Luau::BytecodeEncoder
+{
+    uint8_t key;
+
+    void encode(uint32_t* data, size_t count) override
+    {
+        for (size_t i = 0; i < count;)
+        {
+            uint8_t op = LUAU_INSN_OP(data[i]);
+            int length = Luau::getOpLength(LuauOpcode(op));
+            data[i] = (data[i] & ~0xffu) | uint8_t(op * key);
+            i += length;
+        }
+    }
+};
+
+int main(int argc, char** argv)
+{
+    if (argc < 5)
+        return 1;
+
+    // syntax like @native and the newest bytecode are behind flags
+    for (Luau::FValue<bool>* flag = Luau::FValue<bool>::list; flag; flag = flag->next)
+        if (strncmp(flag->name, "Luau", 4) == 0)
+            flag->value = true;
+
+    std::ifstream in(argv[1]);
+    std::stringstream source;
+    source << in.rdbuf();
+
+    Luau::CompileOptions options;
+    options.optimizationLevel = atoi(argv[3]);
+    options.debugLevel = atoi(argv[4]);
+    options.typeInfoLevel = 1;
+    static const char* userdataTypes[] = {"Vector3", "CFrame", nullptr};
+    options.userdataTypes = userdataTypes;
+
+    KeyEncoder encoder;
+    encoder.key = argc > 5 ? uint8_t(atoi(argv[5])) : 1;
+    std::string bytecode = Luau::compile(source.str(), options, {}, argc > 5 ? &encoder : nullptr);
+
+    std::ofstream out(argv[2], std::ios::binary);
+    out << bytecode;
+    // a version of 0 means the source didn't compile
+    return bytecode[0] == 0;
+}
@@ -0,0 +1,32 @@
+#!/bin/sh
+# Regenerates the bytecode of every fixture from its source.
+#
+# The fixtures were compiled by compile.cpp against Luau 0.640 (the source in the
+# luau0-src 0.10.3+luau640 crate), which emits bytecode version 6 and types version 3,
+# with every `Luau*` flag enabled, type info level 1 and Vector3 and CFrame as userdata types.
+# Each <name>.luau is compiled at optimization levels 0, 1 and 2 to
+#   <name>.O<n>.bc         debug level 1
+#   <name>.O<n>.g2.bc      debug level 2, with local names
+#   <name>.O<n>.key203.bc  debug level 1, op codes encoded with 227 which key 203 decodes
+#
+# usage: generate.sh <luau source directory>
+set -eu
+
+luau=${1:?usage: generate.sh <luau source directory, i.e. luau0-src-0.10.3+luau640/luau>}
+fixtures=$(cd "$(dirname "$0")" && pwd)
+compile=$(mktemp -d)/compile
+trap 'rm -r "$(dirname "$compile")"' EXIT
+
+${CXX:-c++} -O1 -std=c++17 \
+    -I "$luau/Common/include" -I "$luau/Ast/include" -I "$luau/Compiler/include" \
+    "$fixtures/compile.cpp" "$luau"/Ast/src/*.cpp "$luau"/Compiler/src/*.cpp \
+    -o "$compile"
+
+for source in "$fixtures"/*.luau; do
+    name=${source%.luau}
+    for level in 0 1 2; do
+        "$compile" "$source" "$name.O$level.bc" $level 1
+        "$compile" "$source" "$name.O$level.g2.bc" $level 2
+        "$compile" "$source" "$name.O$level.key203.bc" $level 1 227
+    done
+done
@@ -0,0 +1,60 @@
+// Functions that go over the limits are stubbed out instead of failing the whole chunk.
+
+use std::{
+    sync::{atomic::AtomicBool, Arc},
+    time::Duration,
+};
+
+use luau_lifter::{decompile_bytecode_with_options, DecompileLimits, DecompileOptions};
+use mlua::Compiler;
+
+const SOURCE: &str = r#"local function add(a, b)
+	return a + b
+end
+print(add(1, 2))"#;
+
+fn decompile(limits: DecompileLimits) -> String {
+    let bytecode = Compiler::new().set_debug_level(2).compile(SOURCE);
+    let options = DecompileOptions {
+        limits,
+        ..Default::default()
+    };
+    decompile_bytecode_with_options(&bytecode, 1, &options).unwrap()
+}
+
+#[test]
+fn within_limits() {
+    let limits = DecompileLimits {
+        timeout: Some(Duration::from_secs(60)),
+        cancel: Some(Arc::new(AtomicBool::new(false))),
+        ..Default::default()
+    };
+    assert_eq!(decompile(limits), SOURCE);
+}
+
+#[test]
+fn zero_timeout() {
+    let limits = DecompileLimits {
+        timeout: Some(Duration::ZERO),
+        ..Default::default()
+    };
+    assert_eq!(decompile(limits), "-- decompilation timed out");
+}
+
+#[test]
+fn max_iterations() {
+    let limits = DecompileLimits {
+        max_iterations: 0,
+        ..Default::default()
+    };
+    assert_eq!(decompile(limits), "-- decompilation timed out");
+}
+
+#[test]
+fn cancelled() {
+    let limits = DecompileLimits {
+        cancel: Some(Arc::new(AtomicBool::new(true))),
+        ..Default::default()
+    };
+    assert_eq!(decompile(limits), "-- decompilation timed out");
+}
@@ -0,0 +1,152 @@
+// Each Roblox pass is run on its own, and compared against the output with every pass off.
+// The sources are compiled with debug level 2 so locals keep their names.
+
+use luau_lifter::{decompile_bytecode_with_options, DecompileOptions, RobloxPasses};
+use mlua::Compiler;
+
+const NO_PASSES: RobloxPasses = RobloxPasses {
+    hoist_services: false,
+    collapse_find_chains: false,
+    group_instance_properties: false,
+};
+
+fn decompile(source: &str, roblox: RobloxPasses) -> String {
+    let bytecode = Compiler::new().set_debug_level(2).compile(source);
+    let options = DecompileOptions {
+        roblox,
+        ..Default::default()
+    };
+    decompile_bytecode_with_options(&bytecode, 1, &options).unwrap()
+}
+
+fn hoist_services(source: &str) -> (String, String) {
+    let passes = RobloxPasses {
+        hoist_services: true,
+        ..NO_PASSES
+    };
+    (decompile(source, passes), decompile(source, NO_PASSES))
+}
+
+fn collapse_find_chains(source: &str) -> (String, String) {
+    let passes = RobloxPasses {
+        collapse_find_chains: true,
+        ..NO_PASSES
+    };
+    (decompile(source, passes), decompile(source, NO_PASSES))
+}
+
+fn group_instance_properties(source: &str) -> (String, String) {
+    let passes = RobloxPasses {
+        group_instance_properties: true,
+        ..NO_PASSES
+    };
+    (decompile(source, passes), decompile(source, NO_PASSES))
+}
+
+#[test]
+fn hoist_services_reuses_declaration() {
+    let source = r#"local Players = game:GetService("Players")
+Players.PlayerAdded:Connect(function(player)
+	print(game:GetService("Players"):GetPlayers())
+end)
+print(game:GetService("Players").MaxPlayers)"#;
+    let (on, off) = hoist_services(source);
+    assert_eq!(
+        on,
+        r#"local Players = game:GetService("Players")
+Players.PlayerAdded:Connect(function(player)
+	-- upvalues: (copy) Players
+	print(Players:GetPlayers())
+end)
+print(Players.MaxPlayers)"#
+    );
+    assert_eq!(
+        off,
+        r#"local Players = game:GetService("Players")
+Players.PlayerAdded:Connect(function(player)
+	print(game:GetService("Players"):GetPlayers())
+end)
+print(game:GetService("Players").MaxPlayers)"#
+    );
+}
+
+#[test]
+fn hoist_services_captures_new_local() {
+    let source = r#"local function onJoin(player)
+	game:GetService("ReplicatedStorage").Joined:FireAllClients(player)
+end
+game:GetService("ReplicatedStorage").Ready:FireAllClients()
+game:GetService("Players").PlayerAdded:Connect(onJoin)"#;
+    let (on, off) = hoist_services(source);
+    assert_eq!(
+        on,
+        r#"local ReplicatedStorage = game:GetService("ReplicatedStorage")
+local function onJoin(player)
+	-- upvalues: (copy) ReplicatedStorage
+	ReplicatedStorage.Joined:FireAllClients(player)
+end
+ReplicatedStorage.Ready:FireAllClients()
+game:GetService("Players").PlayerAdded:Connect(onJoin)"#
+    );
+    assert_eq!(off, source);
+}
+
+#[test]
+fn collapse_two_level_chain() {
+    let source = r#"local character = script.Parent:WaitForChild("Character")
+local humanoid = character:WaitForChild("Humanoid")
+print(humanoid.Health)"#;
+    let (on, off) = collapse_find_chains(source);
+    assert_eq!(
+        on,
+        r#"local humanoid = script.Parent:WaitForChild("Character"):WaitForChild("Humanoid")
+print(humanoid.Health)"#
+    );
+    assert_eq!(off, source);
+}
+
+#[test]
+fn collapse_three_level_chain() {
+    let source = r#"local gui = script.Parent:WaitForChild("Gui")
+local frame = gui:FindFirstChild("Frame")
+local button = frame:WaitForChild("Button")
+button.Text = "ok""#;
+    let (on, off) = collapse_find_chains(source);
+    assert_eq!(
+        on,
+        r#"local button = script.Parent:WaitForChild("Gui"):FindFirstChild("Frame"):WaitForChild("Button")
+button.Text = "ok""#
+    );
+    assert_eq!(off, source);
+}
+
+#[test]
+fn group_properties_keeps_parent_last() {
+    let source = r#"local part = Instance.new("Part")
+print("created")
+part.Anchored = true
+part.Parent = workspace
+part.Name = "Floor""#;
+    let (on, off) = group_instance_properties(source);
+    assert_eq!(
+        on,
+        r#"local part = Instance.new("Part")
+part.Anchored = true
+print("created")
+part.Parent = workspace
+part.Name = "Floor""#
+    );
+    assert_eq!(off, source);
+}
+
+#[test]
+fn group_properties_blocked_by_write() {
+    let source = r#"local part = Instance.new("Part")
+local name = getName()
+part.Name = name
+part.Anchored = true
+print(name)"#;
+    let (on, off) = group_instance_properties(source);
+    assert_eq!(on, source);
+    assert_eq!(off, source);
+}
@@ -241,17 +241,34 @@ impl GraphStructurer {
         next: Option<NodeIndex>,
     ) -> bool {
         if node == header {
-            // TODO: only check back edges?
-            if !self
+            // the jump is only a `continue` if the branch leading to it could also
+            // have reached the header through another back edge
+            let other = self
                 .function
-                .predecessor_blocks(header)
-                .filter(|&n| n != entry)
-                .any(|n| {
-                    post_dom
-                        .dominators(entry)
-                        .is_some_and(|mut p| p.contains(&n))
-                })
-            {
+                .predecessor_blocks(entry)
+                .exactly_one()
+                .ok()
+                .and_then(|branch| self.function.conditional_edges(branch))
+                .map(|(then_edge, else_edge)| {
+                    if then_edge.target() == entry {
+                        else_edge.target()
+                    } else {
+                        then_edge.target()
+                    }
+                });
+            // TODO: only check back edges?
+            if !other.is_some_and(|other| {
+                other != header
+                    && self
+                        .function
+                        .predecessor_blocks(header)
+                        .filter(|&n| n != entry && n != other)
+                        .any(|n| {
+                            post_dom
+                                .dominators(other)
+                                .is_some_and(|mut p| p.contains(&n))
+                        })
+            }) {
                 return false;
             }
             let block = &mut self.function.block_mut(entry).unwrap();
@@ -259,6 +276,9 @@ impl GraphStructurer {
         } else if Some(node) == next {
             let block = &mut self.function.block_mut(entry).unwrap();
             block.push(ast::Break {}.into());
+        } else {
+            // the jump stays within the loop, it isn't a `break` or a `continue`
+            return false;
         }
         self.function.set_edges(entry, vec![]);
         true
@@ -273,13 +293,15 @@ impl GraphStructurer {
         header: NodeIndex,
         next: Option<NodeIndex>,
     ) -> bool {
+        // an edge to the header is only a `continue` if the other branch still
+        // reaches the header through another back edge
         let then_main_cont = self
             .function
             .predecessor_blocks(header)
             .filter(|&n| n != entry)
             .any(|n| {
                 post_dom
-                    .dominators(then_node)
+                    .dominators(else_node)
                     .is_some_and(|mut p| p.contains(&n))
             });
 
@@ -289,7 +311,7 @@ impl GraphStructurer {
             .filter(|&n| n != entry)
             .any(|n| {
                 post_dom
-                    .dominators(else_node)
+                    .dominators(then_node)
                     .is_some_and(|mut p| p.contains(&n))
             });
 
@@ -38,6 +38,8 @@ impl super::GraphStructurer {
                         right: vec![cond],
                         prefix: true,
                         parallel: false,
+                        line: None,
+                        types: Vec::new(),
                     }
                     .into(),
                 ),
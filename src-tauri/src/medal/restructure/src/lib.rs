@@ -202,11 +202,19 @@ impl GraphStructurer {
         block
     }
 
-    fn collapse(&mut self) {
+    // returns false if `stop` returned true before the graph was collapsed as far as it can be
+    fn collapse(&mut self, stop: &dyn Fn() -> bool) -> bool {
         loop {
-            while self.match_blocks() {}
+            while self.match_blocks() {
+                if stop() {
+                    return false;
+                }
+            }
             if self.function.graph().node_count() == 1 {
-                break;
+                return true;
+            }
+            if stop() {
+                return false;
             }
             // last resort refinement
             let edges = self.function.graph().edge_indices().collect::<Vec<_>>();
@@ -259,15 +267,17 @@ impl GraphStructurer {
                     }
                 }
                 if !changed {
-                    break;
+                    return true;
                 }
             }
         }
     }
 
-    fn structure(mut self) -> ast::Block {
-        self.collapse();
-        if self.function.graph().node_count() != 1 {
+    fn structure(mut self, stop: &dyn Fn() -> bool) -> Option<ast::Block> {
+        if !self.collapse(stop) {
+            return None;
+        }
+        Some(if self.function.graph().node_count() != 1 {
             let mut res_block = ast::Block::default();
             let entry = self.function.entry().unwrap();
             let mut stack = vec![entry];
@@ -291,6 +301,9 @@ impl GraphStructurer {
                             ast::Statement::While(r#while) => {
                                 collect_gotos(&r#while.block.lock(), gotos);
                             }
+                            ast::Statement::Do(r#do) => {
+                                collect_gotos(&r#do.block.lock(), gotos);
+                            }
                             ast::Statement::Repeat(repeat) => {
                                 collect_gotos(&repeat.block.lock(), gotos);
                             }
@@ -349,10 +362,19 @@ impl GraphStructurer {
                     .remove_block(self.function.entry().unwrap())
                     .unwrap(),
             )
-        }
+        })
     }
 }
 
 pub fn lift(function: cfg::function::Function) -> ast::Block {
-    GraphStructurer::new(function).structure()
+    lift_until(function, &|| false).unwrap()
+}
+
+/// Like [`lift`], but gives up and returns `None` if `stop` returns true while collapsing
+/// the graph, which can take a long time for pathological control flow
+pub fn lift_until(
+    function: cfg::function::Function,
+    stop: &dyn Fn() -> bool,
+) -> Option<ast::Block> {
+    GraphStructurer::new(function).structure(stop)
 }